@@ -0,0 +1,18 @@
+//! 打印在线人数变化：`cargo run -p activenow-protocol --example watch -- ws://127.0.0.1:8080/ws?v=2`
+
+use activenow_protocol::{Client, ServerMsg};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let url = std::env::args().nth(1).unwrap_or_else(|| "ws://127.0.0.1:8080/ws?v=2".to_string());
+    let (mut client, hello) = Client::connect(&url).await?;
+    println!("sid={} online={}", hello.sid, hello.count);
+    while let Some(msg) = client.next().await {
+        match msg? {
+            ServerMsg::Sync(s) => println!("online={}", s.count),
+            ServerMsg::Closing(c) => println!("closing: {}", c.reason.as_str()),
+            other => println!("{other:?}"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::{ClientMsg, Hello, ServerMsg};
+
+#[derive(Debug)]
+pub enum Error {
+    Ws(tokio_tungstenite::tungstenite::Error),
+    Decode(serde_json::Error),
+    /// 连接在收到 `hello` 前关闭，或首包不是 `hello`
+    NoHello,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Ws(e) => write!(f, "websocket: {e}"),
+            Error::Decode(e) => write!(f, "decode: {e}"),
+            Error::NoHello => f.write_str("connection closed before hello"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self { Error::Ws(e) }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self { Error::Decode(e) }
+}
+
+/// 最小 WS 客户端：连接后读取 `hello`，之后逐条读取下行消息（仅 `ws://`，JSON 编码）
+pub struct Client {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl Client {
+    /// 连接如 `ws://host:8080/ws?v=2&socket_session_id=...` 的地址并等待 `hello`
+    pub async fn connect(url: &str) -> Result<(Self, Hello), Error> {
+        let (ws, _) = tokio_tungstenite::connect_async(url).await?;
+        let mut client = Client { ws };
+        match client.next().await {
+            Some(Ok(ServerMsg::Hello(hello))) => Ok((client, hello)),
+            Some(Err(e)) => Err(e),
+            _ => Err(Error::NoHello),
+        }
+    }
+
+    /// 下一条下行消息；连接关闭时返回 `None`，非文本帧被忽略
+    pub async fn next(&mut self) -> Option<Result<ServerMsg, Error>> {
+        loop {
+            match self.ws.next().await? {
+                Ok(Message::Text(t)) => return Some(serde_json::from_str(&t).map_err(Error::from)),
+                Ok(Message::Close(_)) => return None,
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+
+    pub async fn send(&mut self, msg: &ClientMsg) -> Result<(), Error> {
+        let text = serde_json::to_string(msg)?;
+        self.ws.send(Message::Text(text.into())).await?;
+        Ok(())
+    }
+
+    pub async fn close(mut self) -> Result<(), Error> {
+        self.ws.close(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+//! ActiveNow WebSocket 协议类型
+//!
+//! 服务端与 Rust 客户端共用：上行 `ClientMsg`、下行 `ServerMsg`（`Hello` / `Sync` / `Delta` / `Geo` / `Closing` / `Error`），
+//! 以及 `EVENT_SINK` 导出的业务事件 `SinkEvent`。开启 `client` 特性（默认）时提供基于 tokio-tungstenite 的 `Client`。
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "client")]
+mod client;
+#[cfg(feature = "client")]
+pub use client::{Client, Error};
+
+/// 上行消息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase", deny_unknown_fields)]
+pub enum ClientMsg {
+    /// 更新去重用的会话 ID
+    #[serde(alias = "updatesid")]
+    UpdateSid {
+        #[serde(alias = "sessionId")]
+        session_id: String,
+    },
+    /// 登录后将本连接关联到用户
+    #[serde(alias = "linkuser")]
+    LinkUser {
+        #[serde(alias = "userId")]
+        user_id: String,
+    },
+    /// 下游实例上报自身在线统计（镜像模式，需 `write:presence` 令牌的观察者连接）
+    Mirror {
+        node: String,
+        count: usize,
+        #[serde(default)]
+        connections: usize,
+        #[serde(default)]
+        sessions: usize,
+        #[serde(default)]
+        users: usize,
+        /// 下游实例版本，登记到上游的集群列表
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<String>,
+    },
+}
+
+/// 下行消息；v2 字段在 v1 连接上缺省
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ServerMsg {
+    Hello(Hello),
+    Sync(Sync),
+    Delta(Delta),
+    Geo(Geo),
+    Closing(Closing),
+    Error(ErrorMsg),
+}
+
+/// 首包
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+    pub sid: String,
+    pub count: usize,
+    pub v: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connections: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<usize>,
+    /// 断线重连令牌，重连时以 `?resume=` 携带可找回原 `sid`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_token: Option<String>,
+    /// 本次连接是否由重连令牌恢复
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub resumed: bool,
+    /// 服务端签名后的会话 ID（配置 `SESSION_SECRET` 时）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// 溢出连接：已计入在线，但后续不推送 `sync`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub overflow: bool,
+    /// 以下由服务端 `HELLO_FIELDS` 开启
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// 本连接可用的协议能力，如 `sync`、`resume`、`update_sid`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features: Option<Vec<String>>,
+}
+
+/// 在线人数变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sync {
+    pub count: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connections: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<usize>,
+    /// 服务端时间戳（毫秒，v2）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ts: Option<u64>,
+}
+
+/// 人数增量（`?events=delta` 订阅且人数达到服务端 `DELTA_THRESHOLD` 时代替 `sync`）；累加到最近一次已知人数上，并定期以 `sync` 校准
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delta {
+    pub d: i64,
+}
+
+/// 按国家 / 地区的在线分布（`?events=geo` 订阅，服务端 `GEO_HEADER` 开启时定期推送）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Geo {
+    /// 按在线数降序
+    pub countries: Vec<CountryCount>,
+    /// 无法识别国家的在线连接数
+    pub unknown: usize,
+    pub ts: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CountryCount {
+    /// ISO 3166-1 alpha-2 大写代码，如 `DE`
+    pub country: String,
+    pub online: usize,
+}
+
+/// 服务端主动断开前的通知
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Closing {
+    pub reason: CloseReason,
+    /// 建议的重连等待；`None` 表示不应自动重连
+    pub retry_after_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// 上行消息被拒绝
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorMsg {
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// 服务端主动断开原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseReason {
+    /// 排空迁移
+    Drain,
+    /// 进程退出
+    Shutdown,
+    /// 被管理员踢出
+    Kicked,
+    /// 超过空闲时限未收到任何帧
+    Timeout,
+    /// 会话或 IP 被封禁
+    Banned,
+}
+
+impl CloseReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CloseReason::Drain => "drain",
+            CloseReason::Shutdown => "shutdown",
+            CloseReason::Kicked => "kicked",
+            CloseReason::Timeout => "timeout",
+            CloseReason::Banned => "banned",
+        }
+    }
+
+    /// Close 帧关闭码：迁移/退出为 1001（Going Away），踢出、超时、封禁使用私有码 4001、4002、4003
+    pub fn close_code(self) -> u16 {
+        match self {
+            CloseReason::Drain | CloseReason::Shutdown => 1001,
+            CloseReason::Kicked => 4001,
+            CloseReason::Timeout => 4002,
+            CloseReason::Banned => 4003,
+        }
+    }
+}
+
+/// 对外导出的业务事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SinkEvent {
+    Connected { sid: String, session_id: String, origin: Option<String>, ts: u64 },
+    Disconnected { sid: String, session_id: String, reason: LeaveReason, duration_ms: u64, ts: u64 },
+    IdentityLinked { sid: String, session_id: String, user_id: String, ts: u64 },
+    /// 审计：连接经 `updateSid` 变更会话 ID
+    SessionChanged { sid: String, from: String, to: String, ts: u64 },
+    OnlineChanged { count: usize, connections: usize, sessions: usize, ts: u64 },
+    /// 每个 `CHURN_WINDOW` 一条：窗口内有进出的来源的接入 / 断开连接数
+    OriginChurn { origin: String, joins: u64, leaves: u64, live: usize, window_ms: u64, ts: u64 },
+}
+
+/// 连接结束原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaveReason {
+    /// 客户端主动关闭或连接断开
+    ClientClose,
+    Timeout,
+    Kicked,
+    /// 收发失败或上行帧超限
+    Error,
+    Shutdown,
+    Drain,
+    Banned,
+}
+
+impl From<CloseReason> for LeaveReason {
+    fn from(r: CloseReason) -> Self {
+        match r {
+            CloseReason::Drain => Self::Drain,
+            CloseReason::Shutdown => Self::Shutdown,
+            CloseReason::Kicked => Self::Kicked,
+            CloseReason::Timeout => Self::Timeout,
+            CloseReason::Banned => Self::Banned,
+        }
+    }
+}
@@ -0,0 +1,503 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, DefaultBodyLimit, Path, Query, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::assets;
+use crate::bundle::{self, Bundle};
+use crate::cluster;
+use crate::config::MAX_DURATION;
+use crate::events::CloseReason;
+use crate::gateway::{self, AppState, ServiceMode};
+use crate::history::RollupReport;
+use crate::ipfilter::{self, IpLists};
+use crate::meta::{Ban, BanTarget, SocketMetadata};
+use crate::reconnect::Offender;
+use crate::registry::ConnInfo;
+use crate::sink::SinkEvent;
+use crate::synthetic::{self, SyntheticSpec, SyntheticStatus};
+use crate::tokens::{ApiToken, Scope};
+
+/// 管理接口；仅在配置 `ADMIN_TOKEN` 或 `API_TOKENS` 时挂载，需携带 `Authorization: Bearer <token>`（或查询参数 `token=`）。
+/// 会话关联只需 `write:presence`，其余需 `admin`；调用方 IP 先经 `IP_ALLOWLIST` / `IP_DENYLIST` 过滤。
+/// `PRIVACY_MODE` 下不挂载按 sid / 会话查询身份的接口
+pub fn routes(state: AppState, synthetic: bool) -> Router<AppState> {
+    let write = with_scope(Router::new().route("/v1/admin/sessions/{session_id}/link", post(link_session)), state.clone(), Scope::WritePresence);
+    let mut router = Router::new();
+    if synthetic {
+        router = router.route("/v1/admin/synthetic", get(synthetic_status).post(start_synthetic).delete(stop_synthetic));
+    }
+    if !state.privacy_mode {
+        router = router
+            .route("/v1/activity/identity/{sid}", get(get_identity))
+            .route("/v1/activity/session/{session_id}", get(get_session_identities));
+    }
+    router = router
+        .route("/v1/admin/connections", get(list_connections))
+        .route("/v1/admin/connections/{sid}/kick", post(kick_connection))
+        .route("/v1/admin/maintenance", get(get_mode).post(set_mode))
+        .route("/v1/admin/bans", get(list_bans).post(add_ban).delete(remove_ban))
+        .route("/v1/admin/rollup", post(run_rollup))
+        .route("/v1/admin/reconnects", get(list_reconnects))
+        .route("/v1/admin/events", get(recent_events))
+        .route("/v1/admin/fanout", get(get_fanout).post(set_fanout))
+        .route("/v1/admin/log-level", get(get_log_level).put(set_log_level))
+        .route("/v1/admin/tokens", get(list_tokens).post(issue_token))
+        .route("/v1/admin/tokens/{name}", delete(revoke_token))
+        .route("/v1/admin/cluster", get(get_cluster))
+        .route("/v1/admin/ip-filter", get(get_ip_filter).put(set_ip_filter))
+        .route("/v1/admin/export", get(export_bundle))
+        .route("/v1/admin/import", post(import_bundle).layer(DefaultBodyLimit::max(IMPORT_MAX_BYTES)))
+        .route("/dashboard", get(assets::dashboard));
+    with_scope(router, state.clone(), Scope::Admin).merge(write).route_layer(middleware::from_fn_with_state(state, ip_guard))
+}
+
+async fn ip_guard(State(state): State<AppState>, ConnectInfo(addr): ConnectInfo<SocketAddr>, req: Request, next: Next) -> Response {
+    if !state.ip_filter.permits(&gateway::client_ip(req.headers(), addr, state.trust_proxy)) {
+        state.metrics.add("activenow_ip_rejected_total", String::new(), 1);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    next.run(req).await
+}
+
+/// 为已添加的路由加上权限校验（`ADMIN_TOKEN` 拥有全部权限）
+pub fn with_scope(router: Router<AppState>, state: AppState, scope: Scope) -> Router<AppState> {
+    router.route_layer(middleware::from_fn_with_state((state, scope), require_scope))
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenQuery { token: Option<String> }
+
+/// 校验调用方具备 `scope` 权限（`ADMIN_TOKEN` 拥有全部权限）
+async fn require_scope(
+    State((state, scope)): State<(AppState, Scope)>,
+    headers: HeaderMap,
+    Query(q): Query<TokenQuery>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let token = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .or(q.token.as_deref());
+    match state.auth.authorize(&headers, token, scope).await {
+        Ok(_) => next.run(req).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ConnQuery {
+    limit: Option<usize>,
+    offset: Option<usize>,
+    session_id: Option<String>,
+    ip: Option<String>,
+    origin: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ConnPage {
+    total: usize,
+    offset: usize,
+    items: Vec<ConnInfo>,
+}
+
+/// `PRIVACY_MODE` 下只返回总数（`items` 为空），按会话 / IP 查询返回 `403`
+async fn list_connections(State(state): State<AppState>, Query(q): Query<ConnQuery>) -> Result<Json<ConnPage>, StatusCode> {
+    if state.privacy_mode && (q.session_id.is_some() || q.ip.is_some()) { return Err(StatusCode::FORBIDDEN); }
+    let all = state.registry.list(|c| {
+        q.session_id.as_deref().is_none_or(|s| c.session_id == s)
+            && q.ip.as_deref().is_none_or(|ip| c.remote_ip.as_deref() == Some(ip))
+            && q.origin.as_deref().is_none_or(|o| c.origin.as_deref().is_some_and(|co| co.contains(o)))
+    });
+    let offset = q.offset.unwrap_or(0);
+    let limit = q.limit.unwrap_or(100).clamp(1, 1000);
+    let total = all.len();
+    let items = if state.privacy_mode { Vec::new() } else { all.into_iter().skip(offset).take(limit).collect() };
+    Ok(Json(ConnPage { total, offset, items }))
+}
+
+async fn kick_connection(State(state): State<AppState>, Path(sid): Path<String>) -> StatusCode {
+    if state.registry.close(&sid, CloseReason::Kicked) { StatusCode::NO_CONTENT } else { StatusCode::NOT_FOUND }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum IdentityState {
+    Connected,
+    /// 已断开，处于 `LEAVE_GRACE` 宽限期，仍计入在线
+    Grace,
+    /// 有元数据但无连接（如虚拟成员）
+    Detached,
+}
+
+/// 单个访客的排查视图；无房间概念，`connection.last_seen_ms` 为最近一次收到帧（含 Pong）的时刻
+#[derive(Serialize)]
+struct IdentityView {
+    sid: String,
+    state: IdentityState,
+    metadata: SocketMetadata,
+    connection: Option<ConnInfo>,
+}
+
+async fn identity_view(state: &AppState, sid: &str) -> Option<IdentityView> {
+    let metadata = state.meta.get(sid).await;
+    let connection = state.registry.get(sid);
+    let metadata = match (metadata, &connection) {
+        (Some(m), _) => m,
+        // 观察者连接不写入 MetaStore
+        (None, Some(c)) => SocketMetadata { identity: c.sid.clone(), session_id: c.session_id.clone(), user_id: c.user_id.clone(), ..Default::default() },
+        (None, None) => return None,
+    };
+    let state = if connection.is_some() {
+        IdentityState::Connected
+    } else if state.pending_leaves.get(&metadata.session_id).is_some_and(|e| e.0 == sid) {
+        IdentityState::Grace
+    } else {
+        IdentityState::Detached
+    };
+    Some(IdentityView { sid: sid.to_string(), state, metadata, connection })
+}
+
+async fn get_identity(State(state): State<AppState>, Path(sid): Path<String>) -> Response {
+    match identity_view(&state, &sid).await {
+        Some(v) => Json(v).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// 会话下的全部连接（多标签页），含宽限期内的与观察者
+async fn get_session_identities(State(state): State<AppState>, Path(session_id): Path<String>) -> Response {
+    let mut sids = state.meta.sids_for_session(&session_id).await;
+    for c in state.registry.list(|c| c.session_id == session_id) {
+        if !sids.contains(&c.sid) { sids.push(c.sid); }
+    }
+    let mut views = Vec::with_capacity(sids.len());
+    for sid in &sids {
+        if let Some(v) = identity_view(&state, sid).await { views.push(v); }
+    }
+    if views.is_empty() { return StatusCode::NOT_FOUND.into_response(); }
+    views.sort_by_key(|v| v.connection.as_ref().map_or(0, |c| c.connected_at_ms));
+    Json(views).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct LinkReq { user_id: String }
+
+#[derive(Serialize)]
+struct LinkResp { linked: usize }
+
+/// 将会话下所有在线连接关联到用户（如登录回调后由业务后端调用）
+async fn link_session(State(state): State<AppState>, Path(session_id): Path<String>, Json(req): Json<LinkReq>) -> Response {
+    if req.user_id.trim().is_empty() { return StatusCode::BAD_REQUEST.into_response(); }
+    let conns = state.registry.list(|c| c.session_id == session_id && !c.observer);
+    let mut linked = 0;
+    for c in conns {
+        if gateway::link_user(&state, &c.sid, req.user_id.trim().to_string()).await { linked += 1; }
+    }
+    if linked == 0 { return StatusCode::NOT_FOUND.into_response(); }
+    Json(LinkResp { linked }).into_response()
+}
+
+async fn list_bans(State(state): State<AppState>) -> Json<Vec<Ban>> {
+    Json(state.meta.list_bans(state.clock.now_ms()).await)
+}
+
+#[derive(Debug, Deserialize)]
+struct BanReq {
+    target: BanTarget,
+    value: String,
+    reason: Option<String>,
+    /// 缺省为永久
+    duration_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct BanResp {
+    ban: Ban,
+    /// 被立即断开的在线连接数
+    kicked: usize,
+}
+
+/// 新增封禁，如 `{"target":"ip","value":"2001:db8:1:2::/64","duration_secs":3600}`；命中的在线连接以 `banned` 断开
+async fn add_ban(State(state): State<AppState>, Json(req): Json<BanReq>) -> Response {
+    let (_, value) = Ban::key(req.target, req.value.trim());
+    if value.is_empty() { return StatusCode::BAD_REQUEST.into_response(); }
+    let now = state.clock.now_ms();
+    let ban = Ban {
+        target: req.target,
+        value,
+        reason: req.reason,
+        created_ms: now,
+        expires_ms: req.duration_secs.map(|s| now.saturating_add(s.saturating_mul(1000))),
+    };
+    state.meta.add_ban(ban.clone()).await;
+    let hits = state.registry.list(|c| ban.hits(c));
+    let kicked = hits.iter().filter(|c| state.registry.close(&c.sid, CloseReason::Banned)).count();
+    tracing::info!(target = ?ban.target, value = %ban.value, kicked, "ban added");
+    (StatusCode::CREATED, Json(BanResp { ban, kicked })).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct BanKey {
+    target: BanTarget,
+    value: String,
+}
+
+async fn remove_ban(State(state): State<AppState>, Query(key): Query<BanKey>) -> StatusCode {
+    if state.meta.remove_ban(key.target, &key.value).await { StatusCode::NO_CONTENT } else { StatusCode::NOT_FOUND }
+}
+
+/// 高频重连来源（按违规次数降序）
+async fn list_reconnects(State(state): State<AppState>) -> Json<Vec<Offender>> {
+    Json(state.reconnects.offenders(state.clock.now_ms()))
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery { limit: Option<usize> }
+
+/// 最近业务事件（`EVENT_HISTORY` 未开启时 404）
+async fn recent_events(State(state): State<AppState>, Query(q): Query<EventsQuery>) -> Result<Json<Vec<SinkEvent>>, StatusCode> {
+    let recent = state.sink.as_ref().and_then(|s| s.recent.as_ref()).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(recent.latest(q.limit.unwrap_or(100).clamp(1, 1000))))
+}
+
+/// 手动触发一次汇总（与定时任务相同，可重复调用）
+async fn run_rollup(State(state): State<AppState>) -> Json<RollupReport> {
+    Json(state.history.rollup(state.clock.now_ms()))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Fanout { paused: bool }
+
+async fn get_fanout(State(state): State<AppState>) -> Json<Fanout> {
+    Json(Fanout { paused: state.bus.paused() })
+}
+
+/// 紧急开关 `{"paused":true}`：暂停向客户端推送 `sync`，人数统计与事件导出不受影响；恢复时推送一次最新人数
+async fn set_fanout(State(state): State<AppState>, Json(req): Json<Fanout>) -> Json<Fanout> {
+    state.bus.set_paused(req.paused);
+    Json(req)
+}
+
+#[derive(Serialize)]
+struct LogLevel { filter: String }
+
+#[derive(Debug, Deserialize)]
+struct LogLevelReq {
+    filter: String,
+    /// 到期后恢复为调整前的规则；缺省为一直生效
+    duration_secs: Option<u64>,
+}
+
+async fn get_log_level(State(state): State<AppState>) -> Json<LogLevel> {
+    Json(LogLevel { filter: state.log.current() })
+}
+
+/// 调整日志过滤规则，如 `{"filter":"info,activenow::gateway=debug","duration_secs":600}`；语法错误返回 400
+async fn set_log_level(State(state): State<AppState>, Json(req): Json<LogLevelReq>) -> Response {
+    let revert = req.duration_secs.map(std::time::Duration::from_secs);
+    match state.log.set(req.filter.trim(), revert) {
+        Ok(()) => {
+            tracing::info!(filter = %req.filter.trim(), duration_secs = req.duration_secs, "log filter changed");
+            Json(LogLevel { filter: state.log.current() }).into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+async fn synthetic_status(State(state): State<AppState>) -> Json<SyntheticStatus> {
+    Json(state.synthetic.status())
+}
+
+/// 注入虚拟在线成员，如 `{"count":500,"churn_per_min":120,"lifetime_secs":300}`；替换已有注入
+async fn start_synthetic(State(state): State<AppState>, Json(spec): Json<SyntheticSpec>) -> Json<SyntheticStatus> {
+    synthetic::start(&state, spec).await;
+    Json(state.synthetic.status())
+}
+
+async fn stop_synthetic(State(state): State<AppState>) -> StatusCode {
+    synthetic::stop(&state).await;
+    StatusCode::NO_CONTENT
+}
+
+/// 集群节点（本实例与 MetaStore 中有心跳的其他实例 / 镜像下游）
+async fn get_cluster(State(state): State<AppState>) -> Json<cluster::ClusterView> {
+    Json(cluster::view(&state).await)
+}
+
+/// 迁移包可能包含当日全部会话 ID，放宽默认的 2MB 请求体上限
+const IMPORT_MAX_BYTES: usize = 64 << 20;
+
+async fn export_bundle(State(state): State<AppState>) -> Json<Bundle> {
+    Json(bundle::export(&state).await)
+}
+
+/// 导入 `/v1/admin/export` 的输出，与本地数据合并
+async fn import_bundle(State(state): State<AppState>, Json(b): Json<Bundle>) -> Response {
+    if b.format != bundle::FORMAT {
+        return (StatusCode::BAD_REQUEST, format!("unsupported bundle format {}, expected {}", b.format, bundle::FORMAT)).into_response();
+    }
+    let from = b.node.clone();
+    let report = bundle::import(&state, b).await;
+    tracing::info!(from = %from, daily_merged = report.daily_merged, bans = report.bans, samples = report.history.samples, kicked = report.kicked, "bundle imported");
+    Json(report).into_response()
+}
+
+async fn get_mode(State(state): State<AppState>) -> Json<ServiceMode> {
+    Json(state.mode_tx.borrow().clone())
+}
+
+/// 切换服务模式，如 `{"mode":"drain","retry_after_secs":30,"reconnect_to":"wss://b.example.com/ws","drain_secs":60}`；
+/// 秒数超过 `MAX_DURATION` 时 `400`
+async fn set_mode(State(state): State<AppState>, Json(mode): Json<ServiceMode>) -> Response {
+    let max = MAX_DURATION.as_secs();
+    let secs = match &mode {
+        ServiceMode::Normal => vec![],
+        ServiceMode::Maintenance { retry_after_secs } => vec![("retry_after_secs", *retry_after_secs)],
+        ServiceMode::Drain { retry_after_secs, drain_secs, .. } => vec![("retry_after_secs", *retry_after_secs), ("drain_secs", *drain_secs)],
+    };
+    if let Some((field, v)) = secs.into_iter().find(|(_, v)| *v > max) {
+        return (StatusCode::BAD_REQUEST, format!("{field} {v} exceeds {max}")).into_response();
+    }
+    tracing::info!(?mode, "service mode changed");
+    state.mode_tx.send_replace(mode.clone());
+    Json(mode).into_response()
+}
+
+async fn get_ip_filter(State(state): State<AppState>) -> Json<IpLists> {
+    Json(state.ip_filter.lists())
+}
+
+#[derive(Debug, Deserialize)]
+struct IpFilterReq {
+    allow: Option<Vec<String>>,
+    deny: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+struct IpFilterResp {
+    #[serde(flatten)]
+    lists: IpLists,
+    /// 因新名单断开的现有连接数
+    kicked: usize,
+}
+
+/// 替换允许 / 拒绝名单（省略的一项保持不变），并断开不再放行的现有连接；会拒绝调用方自身时返回 `409`
+async fn set_ip_filter(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<IpFilterReq>,
+) -> Response {
+    let current = state.ip_filter.lists();
+    let parse = |items: Option<Vec<String>>, fallback: Vec<String>| ipfilter::parse_all(&items.unwrap_or(fallback));
+    let (allow, deny) = match (parse(req.allow, current.allow), parse(req.deny, current.deny)) {
+        (Ok(allow), Ok(deny)) => (allow, deny),
+        (Err(bad), _) | (_, Err(bad)) => return (StatusCode::BAD_REQUEST, format!("invalid CIDR: {bad}")).into_response(),
+    };
+    let caller = gateway::client_ip(&headers, addr, state.trust_proxy);
+    if !ipfilter::IpFilter::new(allow.clone(), deny.clone()).permits(&caller) {
+        return (StatusCode::CONFLICT, format!("new lists would block the caller ({caller})")).into_response();
+    }
+    state.ip_filter.set(allow, deny);
+    let lists = state.ip_filter.lists();
+    let hits = state.registry.list(|c| c.remote_ip.as_deref().is_some_and(|ip| !state.ip_filter.permits(ip)));
+    let kicked = hits.iter().filter(|c| state.registry.close(&c.sid, CloseReason::Banned)).count();
+    tracing::info!(allow = ?lists.allow, deny = ?lists.deny, kicked, "ip filter updated");
+    Json(IpFilterResp { lists, kicked }).into_response()
+}
+
+async fn list_tokens(State(state): State<AppState>) -> Json<Vec<ApiToken>> {
+    Json(state.tokens.list())
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenReq {
+    name: String,
+    scopes: Vec<Scope>,
+}
+
+#[derive(Serialize)]
+struct IssuedToken {
+    /// 明文仅返回这一次
+    token: String,
+    #[serde(flatten)]
+    info: ApiToken,
+}
+
+/// 签发令牌，如 `{"name":"ci","scopes":["read:presence"]}`；同名令牌被替换。仅保存在内存，重启后需重新签发（长期令牌写入 `API_TOKENS`）
+async fn issue_token(State(state): State<AppState>, Json(req): Json<TokenReq>) -> Response {
+    let name = req.name.trim().to_string();
+    if name.is_empty() || req.scopes.is_empty() { return StatusCode::BAD_REQUEST.into_response(); }
+    let (token, info) = state.tokens.issue(name, req.scopes, state.clock.now_ms());
+    tracing::info!(name = %info.name, scopes = ?info.scopes, "api token issued");
+    (StatusCode::CREATED, Json(IssuedToken { token, info })).into_response()
+}
+
+async fn revoke_token(State(state): State<AppState>, Path(name): Path<String>) -> StatusCode {
+    if state.tokens.revoke(&name) {
+        tracing::info!(%name, "api token revoked");
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use activenow_protocol::{Client, CloseReason as WireReason, ServerMsg};
+
+    use crate::test_support::{self, next_matching, TestServer};
+
+    /// 封禁会话：在线连接收到 `closing{reason:"banned"}`，之后的握手被拒绝
+    #[tokio::test]
+    async fn session_ban_kicks_and_rejects() {
+        let server = TestServer::start(test_support::config()).await;
+        let (mut client, _) = server.connect("socket_session_id=spam").await;
+        let (status, body) = server.http("POST", "/v1/admin/bans", Some(r#"{"target":"session","value":"spam","duration_secs":60}"#)).await;
+        assert_eq!(status, 201);
+        assert!(body.contains(r#""kicked":1"#), "{body}");
+        let reason = next_matching(&mut client, |m| match m { ServerMsg::Closing(c) => Some(c.reason), _ => None }).await;
+        assert_eq!(reason, Some(WireReason::Banned));
+        assert!(Client::connect(&server.ws_url("socket_session_id=spam")).await.is_err());
+        server.wait_online(0).await;
+    }
+
+    #[tokio::test]
+    async fn set_mode_rejects_oversized_secs() {
+        let server = TestServer::start(test_support::config()).await;
+        let (status, _) = server.http("POST", "/v1/admin/maintenance", Some(r#"{"mode":"drain","retry_after_secs":30,"drain_secs":18446744073709551615}"#)).await;
+        assert_eq!(status, 400);
+        let (status, _) = server.http("POST", "/v1/admin/maintenance", Some(r#"{"mode":"maintenance","retry_after_secs":30}"#)).await;
+        assert_eq!(status, 200);
+        assert!(Client::connect(&server.ws_url("socket_session_id=s1")).await.is_err());
+    }
+
+    /// 虚拟成员计入人数；`churn_per_min` 按上限截断；DELETE 全部移除
+    #[tokio::test]
+    async fn synthetic_presence_counts_and_stops() {
+        let mut cfg = test_support::config();
+        cfg.synthetic_presence = true;
+        let server = TestServer::start(cfg).await;
+        let (status, body) = server.http("POST", "/v1/admin/synthetic", Some(r#"{"count":5,"churn_per_min":1e300}"#)).await;
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""churn_per_min":60000.0"#), "{body}");
+        let (client, hello) = server.connect("socket_session_id=real").await;
+        assert!(hello.count >= 1);
+        let (status, _) = server.http("DELETE", "/v1/admin/synthetic", None).await;
+        assert_eq!(status, 204);
+        server.wait_online(1).await;
+        client.close().await.unwrap();
+        server.wait_online(0).await;
+    }
+}
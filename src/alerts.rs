@@ -0,0 +1,85 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use crate::gateway::OnlineStats;
+use crate::supervisor::Supervisor;
+use crate::time::SharedClock;
+use crate::webhook;
+
+/// 在线人数告警配置
+#[derive(Debug, Clone, Default)]
+pub struct AlertConfig {
+    /// 上穿阈值（升序）
+    pub thresholds: Vec<usize>,
+    /// 在线持续为 0 达到该时长时告警
+    pub zero_after: Option<Duration>,
+    /// 同一告警两次触发的最小间隔
+    pub cooldown: Duration,
+    pub webhook: Option<String>,
+    /// `EVENT_SIGNING_SECRET`
+    pub signing_secret: Option<String>,
+}
+
+impl AlertConfig {
+    pub fn enabled(&self) -> bool { !self.thresholds.is_empty() || self.zero_after.is_some() }
+}
+
+/// 监听在线人数，触发阈值 / 长时间归零告警（日志 + 可选 webhook）
+pub fn spawn(tasks: &Arc<Supervisor>, cfg: AlertConfig, rx: watch::Receiver<OnlineStats>, clock: SharedClock) {
+    tasks.spawn("alerts", move || watch_online(cfg.clone(), rx.clone(), clock.clone()));
+}
+
+async fn watch_online(cfg: AlertConfig, mut rx: watch::Receiver<OnlineStats>, clock: SharedClock) {
+    let mut last_fired: Vec<Option<Instant>> = vec![None; cfg.thresholds.len()];
+    let mut zero_fired_at: Option<Instant> = None;
+    let mut prev = rx.borrow_and_update().count;
+    let mut zero_since = (prev == 0).then(Instant::now);
+    let mut zero_armed = true;
+    loop {
+        let zero_deadline = cfg.zero_after.zip(zero_since).filter(|_| zero_armed).map(|(d, t)| t + d);
+        tokio::select! {
+            changed = rx.changed() => {
+                if changed.is_err() { break; }
+                let now = rx.borrow_and_update().count;
+                for (i, t) in cfg.thresholds.iter().enumerate() {
+                    if prev < *t && now >= *t && cooled(last_fired[i], cfg.cooldown) {
+                        last_fired[i] = Some(Instant::now());
+                        fire(&cfg, json!({ "alarm": "online_high", "threshold": t, "online": now, "ts": clock.now_ms() }));
+                    }
+                }
+                if now == 0 && prev != 0 { zero_since = Some(Instant::now()); }
+                if now != 0 { zero_since = None; zero_armed = true; }
+                prev = now;
+            }
+            _ = async { if let Some(at) = zero_deadline { tokio::time::sleep_until(at).await } }, if zero_deadline.is_some() => {
+                zero_armed = false;
+                if cooled(zero_fired_at, cfg.cooldown) {
+                    zero_fired_at = Some(Instant::now());
+                    let secs = cfg.zero_after.map(|d| d.as_secs()).unwrap_or_default();
+                    fire(&cfg, json!({ "alarm": "online_zero", "for_secs": secs, "online": 0, "ts": clock.now_ms() }));
+                }
+            }
+        }
+    }
+}
+
+fn cooled(last: Option<Instant>, cooldown: Duration) -> bool {
+    last.is_none_or(|t| t.elapsed() >= cooldown)
+}
+
+fn fire(cfg: &AlertConfig, payload: serde_json::Value) {
+    tracing::warn!(%payload, "online alert");
+    if let Some(url) = cfg.webhook.clone() {
+        let body = payload.to_string();
+        let headers = webhook::signature_headers(cfg.signing_secret.as_deref(), &body);
+        tokio::spawn(async move {
+            if let Err(e) = webhook::post_json(&url, &body, &headers).await {
+                tracing::warn!(error = %e, "alert webhook failed");
+            }
+        });
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::OnceLock;
+
+use axum::{http::header, response::{Html, IntoResponse}};
+
+/// 内置 JS 客户端：同一份核心代码分别包装为经典脚本（`window.ActiveNow`）与 ESM
+const CLIENT_CORE: &str = include_str!("../static/client-core.js");
+
+const CLIENT_IIFE_TAIL: &str = r#"
+  var api = { connect: connect, version: ACTIVENOW_VERSION };
+  window.ActiveNow = api;
+  // <script src=".../client.js" data-auto>：自动连接，并把人数写入 [data-activenow-count] 元素
+  var me = document.currentScript;
+  if (me && me.hasAttribute("data-auto")) {
+    connect({ base: me.src }).onCount(function (n) {
+      document.querySelectorAll("[data-activenow-count]").forEach(function (el) { el.textContent = String(n); });
+    });
+  }
+})();
+"#;
+
+const CLIENT_ESM_TAIL: &str = r#"
+export { connect, ACTIVENOW_VERSION as version };
+export default { connect: connect, version: ACTIVENOW_VERSION };
+"#;
+
+fn render(head: &str, tail: &str) -> String {
+    let core = CLIENT_CORE.replace("__VERSION__", env!("CARGO_PKG_VERSION"));
+    format!("/*! activenow client v{} */\n{}{}{}", env!("CARGO_PKG_VERSION"), head, core, tail)
+}
+
+fn js(body: &'static str) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "application/javascript; charset=utf-8"),
+            (header::CACHE_CONTROL, "public, max-age=3600"),
+            (header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+        ],
+        body,
+    )
+}
+
+pub async fn client_js() -> impl IntoResponse {
+    static BODY: OnceLock<String> = OnceLock::new();
+    js(BODY.get_or_init(|| render("(function () {\n", CLIENT_IIFE_TAIL)))
+}
+
+pub async fn client_esm() -> impl IntoResponse {
+    static BODY: OnceLock<String> = OnceLock::new();
+    js(BODY.get_or_init(|| render("", CLIENT_ESM_TAIL)))
+}
+
+/// 可嵌入 iframe 的在线人数挂件，如 `<iframe src="https://host/widget?theme=dark">`
+pub async fn widget() -> impl IntoResponse {
+    ([(header::CACHE_CONTROL, "public, max-age=3600")], Html(include_str!("../static/widget.html")))
+}
+
+/// 内置监控面板（挂在管理路由下，浏览器以 `?token=` 访问）
+pub async fn dashboard() -> impl IntoResponse {
+    ([(header::CACHE_CONTROL, "no-store")], Html(include_str!("../static/dashboard.html")))
+}
@@ -0,0 +1,76 @@
+use async_trait::async_trait;
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use std::sync::Arc;
+
+use crate::gateway::WebQuery;
+use crate::sign;
+use crate::tokens::{self, ApiTokens, Scope};
+
+/// 鉴权通过后的调用方身份
+#[derive(Debug, Clone, Default)]
+pub struct Identity {
+    /// 业务侧用户标识（匿名访客为 `None`）
+    pub user_id: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    /// 缺少或无效的凭据
+    Unauthorized,
+    /// 凭据有效但无权访问
+    Forbidden,
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        match self {
+            AuthError::Unauthorized => StatusCode::UNAUTHORIZED.into_response(),
+            AuthError::Forbidden => StatusCode::FORBIDDEN.into_response(),
+        }
+    }
+}
+
+/// 可替换的鉴权钩子：WS 握手与管理接口均经由此处
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    /// WS 升级前调用；默认放行所有访客
+    async fn authenticate(&self, _headers: &HeaderMap, _query: &WebQuery) -> Result<Identity, AuthError> {
+        Ok(Identity::default())
+    }
+
+    /// 管理接口调用；`token` 为 `Authorization: Bearer` 或查询参数 `token` 的值
+    async fn authenticate_admin(&self, headers: &HeaderMap, token: Option<&str>) -> Result<Identity, AuthError>;
+
+    /// 需要 `scope` 权限的 REST 调用；默认一律按管理接口校验
+    async fn authorize(&self, headers: &HeaderMap, token: Option<&str>, _scope: Scope) -> Result<Identity, AuthError> {
+        self.authenticate_admin(headers, token).await
+    }
+}
+
+/// 默认实现：访客匿名放行，REST 接口校验 `ADMIN_TOKEN`（全部权限）或带对应权限的 API 令牌
+pub struct TokenAuth {
+    pub admin_token: Option<String>,
+    pub tokens: Arc<ApiTokens>,
+}
+
+#[async_trait]
+impl AuthProvider for TokenAuth {
+    async fn authenticate_admin(&self, headers: &HeaderMap, token: Option<&str>) -> Result<Identity, AuthError> {
+        self.authorize(headers, token, Scope::Admin).await
+    }
+
+    async fn authorize(&self, _headers: &HeaderMap, token: Option<&str>, scope: Scope) -> Result<Identity, AuthError> {
+        let Some(got) = token else { return Err(AuthError::Unauthorized) };
+        // 比较两者的哈希，耗时与令牌内容及长度无关
+        let admin = self.admin_token.as_deref().is_some_and(|t| sign::ct_eq(tokens::hash(t).as_bytes(), tokens::hash(got).as_bytes()));
+        if admin || self.tokens.check(got, scope).is_some() {
+            Ok(Identity::default())
+        } else {
+            Err(AuthError::Forbidden)
+        }
+    }
+}
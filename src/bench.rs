@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use activenow_protocol::{Client, ClientMsg, ServerMsg};
+use rand::Rng;
+
+use crate::config::parse_duration;
+
+const USAGE: &str = "usage: activenow bench [--url ws://127.0.0.1:8080/ws?v=2] [--connections 100] [--duration 30s] [--ramp 5s] \
+[--update-every 0] [--churn-every 0] [--max-drop-rate 1]";
+
+/// 压测参数
+#[derive(Debug, Clone)]
+pub struct BenchOpts {
+    pub url: String,
+    pub connections: usize,
+    pub duration: Duration,
+    /// 在该时长内均匀建立全部连接
+    pub ramp: Duration,
+    /// 每个连接按该间隔发送 `updateSid`，在自身与相邻连接的会话 ID 间切换以制造人数变化
+    pub update_every: Option<Duration>,
+    /// 每个连接平均存活该时长后断开重连（指数分布）
+    pub churn_every: Option<Duration>,
+    /// 掉线率（含连接失败）超过该值时退出码为 `1`
+    pub max_drop_rate: f64,
+}
+
+impl BenchOpts {
+    pub fn parse(args: impl Iterator<Item = String>, port: u16) -> Result<Self, String> {
+        let mut opts = BenchOpts {
+            url: format!("ws://127.0.0.1:{port}/ws?v=2"),
+            connections: 100,
+            duration: Duration::from_secs(30),
+            ramp: Duration::from_secs(5),
+            update_every: None,
+            churn_every: None,
+            max_drop_rate: 1.0,
+        };
+        let mut args = args;
+        while let Some(flag) = args.next() {
+            if flag == "-h" || flag == "--help" { return Err(USAGE.to_string()); }
+            let value = args.next().ok_or_else(|| format!("{flag} requires a value\n{USAGE}"))?;
+            let bad = || format!("invalid value for {flag}: {value:?}");
+            let dur = |v: &str| parse_duration(v).ok_or_else(bad);
+            match flag.as_str() {
+                "--url" => opts.url = value.clone(),
+                "--connections" => opts.connections = value.parse().ok().filter(|n| *n > 0).ok_or_else(bad)?,
+                "--duration" => opts.duration = dur(&value).and_then(|d| if d.is_zero() { Err(bad()) } else { Ok(d) })?,
+                "--ramp" => opts.ramp = dur(&value)?,
+                "--update-every" => opts.update_every = Some(dur(&value)?).filter(|d| !d.is_zero()),
+                "--churn-every" => opts.churn_every = Some(dur(&value)?).filter(|d| !d.is_zero()),
+                "--max-drop-rate" => opts.max_drop_rate = value.parse().ok().filter(|r: &f64| (0.0..=1.0).contains(r)).ok_or_else(bad)?,
+                _ => return Err(format!("unknown option {flag}\n{USAGE}")),
+            }
+        }
+        if !opts.url.starts_with("ws://") { return Err(format!("--url must be a ws:// URL, got {:?}", opts.url)); }
+        Ok(opts)
+    }
+
+    fn session_url(&self, session_id: &str) -> String {
+        let sep = if self.url.contains('?') { '&' } else { '?' };
+        format!("{}{sep}socket_session_id={session_id}", self.url)
+    }
+}
+
+/// 单个连接任务的统计，结束后合并
+#[derive(Debug, Default)]
+struct WorkerStats {
+    /// 握手到收到 `hello` 的耗时（微秒）
+    connect_us: Vec<u64>,
+    /// 收到 `sync` 的本地时刻减服务端 `ts`（毫秒）；需 `v=2` 且两端时钟一致
+    sync_ms: Vec<u64>,
+    connects: usize,
+    connect_failures: usize,
+    /// 非压测主动发起的断开（服务端关闭、`closing`、读写错误）
+    drops: usize,
+    churns: usize,
+    updates: usize,
+    errors: BTreeMap<String, usize>,
+}
+
+impl WorkerStats {
+    fn merge(&mut self, other: WorkerStats) {
+        self.connect_us.extend(other.connect_us);
+        self.sync_ms.extend(other.sync_ms);
+        self.connects += other.connects;
+        self.connect_failures += other.connect_failures;
+        self.drops += other.drops;
+        self.churns += other.churns;
+        self.updates += other.updates;
+        for (k, n) in other.errors { *self.errors.entry(k).or_default() += n; }
+    }
+}
+
+/// 进度输出用的实时计数
+#[derive(Default)]
+struct Live {
+    open: AtomicUsize,
+    syncs: AtomicU64,
+    last_count: AtomicUsize,
+}
+
+/// `activenow bench`：对目标实例建立大量连接，输出连接延迟、sync 延迟与掉线率；返回进程退出码
+pub async fn run(opts: BenchOpts) -> i32 {
+    println!(
+        "bench {} connections={} duration={}s ramp={}s update_every={} churn_every={}",
+        opts.url,
+        opts.connections,
+        opts.duration.as_secs_f64(),
+        opts.ramp.as_secs_f64(),
+        opts.update_every.map_or("-".to_string(), |d| format!("{}s", d.as_secs_f64())),
+        opts.churn_every.map_or("-".to_string(), |d| format!("{}s", d.as_secs_f64())),
+    );
+    let opts = Arc::new(opts);
+    let live = Arc::new(Live::default());
+    let started = Instant::now();
+    let deadline = tokio::time::Instant::now() + opts.ramp + opts.duration;
+    let workers: Vec<_> = (0..opts.connections)
+        .map(|i| {
+            let delay = opts.ramp.mul_f64(i as f64 / opts.connections as f64);
+            tokio::spawn(worker(i, delay, deadline, opts.clone(), live.clone()))
+        })
+        .collect();
+
+    let progress = {
+        let live = live.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(Duration::from_secs(5));
+            tick.tick().await;
+            loop {
+                tick.tick().await;
+                println!(
+                    "  t={:>5.1}s open={} online={} syncs={}",
+                    started.elapsed().as_secs_f64(),
+                    live.open.load(Ordering::Relaxed),
+                    live.last_count.load(Ordering::Relaxed),
+                    live.syncs.load(Ordering::Relaxed),
+                );
+            }
+        })
+    };
+
+    let mut total = WorkerStats::default();
+    for w in workers {
+        match w.await {
+            Ok(s) => total.merge(s),
+            Err(e) => *total.errors.entry(format!("worker panicked: {e}")).or_default() += 1,
+        }
+    }
+    progress.abort();
+    report(&opts, total, started.elapsed())
+}
+
+async fn worker(i: usize, delay: Duration, deadline: tokio::time::Instant, opts: Arc<BenchOpts>, live: Arc<Live>) -> WorkerStats {
+    let mut stats = WorkerStats::default();
+    tokio::time::sleep(delay).await;
+    let own = format!("bench-{i}");
+    let partner = format!("bench-{}", i ^ 1);
+    while tokio::time::Instant::now() < deadline {
+        let t0 = Instant::now();
+        let connected = tokio::time::timeout_at(deadline, Client::connect(&opts.session_url(&own))).await;
+        let mut client = match connected {
+            Err(_) => break,
+            Ok(Ok((client, _))) => client,
+            Ok(Err(e)) => {
+                stats.connect_failures += 1;
+                *stats.errors.entry(format!("connect: {e}")).or_default() += 1;
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        stats.connects += 1;
+        stats.connect_us.push(t0.elapsed().as_micros() as u64);
+        live.open.fetch_add(1, Ordering::Relaxed);
+        let end = match opts.churn_every {
+            Some(mean) => deadline.min(tokio::time::Instant::now() + mean.mul_f64(-(1.0 - rand::thread_rng().gen::<f64>()).ln())),
+            None => deadline,
+        };
+        let mut update = opts.update_every.map(|every| {
+            let mut t = tokio::time::interval_at(tokio::time::Instant::now() + every.mul_f64(rand::thread_rng().gen()), every);
+            t.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            t
+        });
+        let mut swapped = false;
+        let dropped = loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(end) => break None,
+                _ = async { update.as_mut().unwrap().tick().await }, if update.is_some() => {
+                    swapped = !swapped;
+                    let session_id = if swapped { partner.clone() } else { own.clone() };
+                    if let Err(e) = client.send(&ClientMsg::UpdateSid { session_id }).await { break Some(format!("send: {e}")); }
+                    stats.updates += 1;
+                }
+                msg = client.next() => match msg {
+                    Some(Ok(ServerMsg::Sync(s))) => {
+                        live.syncs.fetch_add(1, Ordering::Relaxed);
+                        live.last_count.store(s.count, Ordering::Relaxed);
+                        if let Some(ts) = s.ts { stats.sync_ms.push(now_ms().saturating_sub(ts)); }
+                    }
+                    Some(Ok(ServerMsg::Closing(c))) => break Some(format!("closing: {}", c.reason.as_str())),
+                    Some(Ok(ServerMsg::Error(e))) => { *stats.errors.entry(format!("error: {}", e.code)).or_default() += 1; }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => break Some(format!("read: {e}")),
+                    None => break Some("closed by server".to_string()),
+                },
+            }
+        };
+        live.open.fetch_sub(1, Ordering::Relaxed);
+        match dropped {
+            Some(why) => {
+                stats.drops += 1;
+                *stats.errors.entry(why).or_default() += 1;
+            }
+            None => {
+                if end < deadline { stats.churns += 1; }
+                let _ = client.close().await;
+            }
+        }
+    }
+    stats
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// 升序样本的分位数
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() { return 0; }
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+fn report(opts: &BenchOpts, mut s: WorkerStats, elapsed: Duration) -> i32 {
+    s.connect_us.sort_unstable();
+    s.sync_ms.sort_unstable();
+    let attempts = s.connects + s.connect_failures;
+    let drop_rate = if attempts == 0 { 1.0 } else { (s.drops + s.connect_failures) as f64 / attempts as f64 };
+    let ms = |us: u64| us as f64 / 1000.0;
+    println!("done in {:.1}s", elapsed.as_secs_f64());
+    println!("  connects        {} ok, {} failed, {} churned, {} updates sent", s.connects, s.connect_failures, s.churns, s.updates);
+    println!(
+        "  connect latency p50={:.1}ms p95={:.1}ms p99={:.1}ms max={:.1}ms",
+        ms(percentile(&s.connect_us, 0.5)),
+        ms(percentile(&s.connect_us, 0.95)),
+        ms(percentile(&s.connect_us, 0.99)),
+        ms(s.connect_us.last().copied().unwrap_or(0)),
+    );
+    if s.sync_ms.is_empty() {
+        println!("  sync latency    - (no sync with ts; use ?v=2)");
+    } else {
+        println!(
+            "  sync latency    p50={}ms p95={}ms p99={}ms max={}ms ({} syncs)",
+            percentile(&s.sync_ms, 0.5),
+            percentile(&s.sync_ms, 0.95),
+            percentile(&s.sync_ms, 0.99),
+            s.sync_ms.last().copied().unwrap_or(0),
+            s.sync_ms.len(),
+        );
+    }
+    println!("  drop rate       {:.2}% ({} drops, {} failed connects)", drop_rate * 100.0, s.drops, s.connect_failures);
+    for (why, n) in &s.errors {
+        println!("    {n:>6}  {why}");
+    }
+    if drop_rate > opts.max_drop_rate {
+        eprintln!("drop rate {:.2}% exceeds --max-drop-rate {:.2}%", drop_rate * 100.0, opts.max_drop_rate * 100.0);
+        return 1;
+    }
+    0
+}
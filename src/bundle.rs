@@ -0,0 +1,64 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cluster;
+use crate::events::CloseReason;
+use crate::gateway::AppState;
+use crate::history::{HistoryDump, HistoryImport};
+use crate::meta::{Ban, BanTarget, DailyState};
+
+/// 迁移包格式版本；导入时不一致则拒绝
+pub const FORMAT: u32 = 1;
+
+/// 迁移包：当日统计、封禁与在线历史，用于实例间迁移存储而不丢历史
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bundle {
+    pub format: u32,
+    /// 导出实例的节点 ID 与版本，仅供追溯
+    pub node: String,
+    pub version: String,
+    pub exported_ms: u64,
+    pub daily: DailyState,
+    pub bans: Vec<Ban>,
+    pub history: HistoryDump,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    /// 迁移包的当日统计与本实例同日时合并
+    pub daily_merged: bool,
+    /// 新增的封禁数；已过期或本地已有的跳过
+    pub bans: usize,
+    pub history: HistoryImport,
+    pub kicked: usize,
+}
+
+pub async fn export(state: &AppState) -> Bundle {
+    let now = state.clock.now_ms();
+    Bundle {
+        format: FORMAT,
+        node: state.node_id.clone(),
+        version: cluster::version(),
+        exported_ms: now,
+        daily: state.meta.daily_snapshot(now).await,
+        bans: state.meta.list_bans(now).await,
+        history: state.history.dump(),
+    }
+}
+
+/// 合并迁移包，本地已有数据优先；重复导入同一迁移包结果不变
+pub async fn import(state: &AppState, bundle: Bundle) -> ImportReport {
+    let now = state.clock.now_ms();
+    let daily_merged = state.meta.merge_daily(bundle.daily, now).await;
+    let existing: HashSet<(BanTarget, String)> = state.meta.list_bans(now).await.into_iter().map(|b| Ban::key(b.target, &b.value)).collect();
+    let mut added = Vec::new();
+    for ban in bundle.bans.into_iter().filter(|b| b.active(now) && !existing.contains(&Ban::key(b.target, &b.value))) {
+        state.meta.add_ban(ban.clone()).await;
+        added.push(ban);
+    }
+    let hits = state.registry.list(|c| added.iter().any(|b| b.hits(c)));
+    let kicked = hits.iter().filter(|c| state.registry.close(&c.sid, CloseReason::Banned)).count();
+    let history = state.history.restore(bundle.history);
+    ImportReport { daily_merged, bans: added.len(), history, kicked }
+}
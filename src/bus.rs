@@ -0,0 +1,101 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::watch;
+
+use crate::codec::SharedCodec;
+use crate::events::SyncFrame;
+use crate::gateway::OnlineStats;
+use crate::metrics::Metrics;
+use crate::shed::LoadShedder;
+use crate::supervisor::Supervisor;
+use crate::time::SharedClock;
+
+/// 在线统计的发布 / 订阅；默认为进程内 `LocalBus`，跨实例部署可替换为集群总线
+pub trait EventBus: Send + Sync {
+    fn publish(&self, stats: OnlineStats);
+    fn current(&self) -> OnlineStats;
+    /// 订阅统计变化（告警、采样、事件导出等后台任务）
+    fn subscribe(&self) -> watch::Receiver<OnlineStats>;
+    /// 订阅预序列化的 `sync` 帧（WS 连接）
+    fn frames(&self) -> watch::Receiver<Arc<SyncFrame>>;
+    /// 暂停 / 恢复 `sync` 推送（统计照常更新）；返回切换前的状态
+    fn set_paused(&self, paused: bool) -> bool;
+    fn paused(&self) -> bool;
+}
+
+/// 基于 `watch` 通道的进程内实现
+pub struct LocalBus {
+    online_tx: watch::Sender<OnlineStats>,
+    frames_rx: watch::Receiver<Arc<SyncFrame>>,
+    paused_tx: watch::Sender<bool>,
+}
+
+impl LocalBus {
+    /// `batch` 内的连续变化合并为一次 `sync` 帧（降载期间不低于 `shed.sync_batch()`），帧按 `codec` 编码
+    pub fn new(batch: Option<Duration>, clock: SharedClock, codec: SharedCodec, metrics: Arc<Metrics>, shed: &LoadShedder, tasks: &Arc<Supervisor>) -> Self {
+        let (online_tx, online_rx) = watch::channel(OnlineStats::default());
+        let (frames_tx, frames_rx) = watch::channel(Arc::new(SyncFrame::new(OnlineStats::default(), 0, clock.now_ms(), codec.as_ref())));
+        let (paused_tx, paused_rx) = watch::channel(false);
+        let (shed_rx, shed_batch) = (shed.subscribe(), shed.sync_batch());
+        tasks.spawn("sync_fanout", move || {
+            let batch = Batch { normal: batch, shed: shed_batch, shedding: shed_rx.clone() };
+            sync_fanout(online_rx.clone(), frames_tx.clone(), paused_rx.clone(), batch, clock.clone(), codec.clone(), metrics.clone())
+        });
+        Self { online_tx, frames_rx, paused_tx }
+    }
+}
+
+impl EventBus for LocalBus {
+    fn publish(&self, stats: OnlineStats) { let _ = self.online_tx.send(stats); }
+    fn current(&self) -> OnlineStats { *self.online_tx.borrow() }
+    fn subscribe(&self) -> watch::Receiver<OnlineStats> { self.online_tx.subscribe() }
+    fn frames(&self) -> watch::Receiver<Arc<SyncFrame>> { self.frames_rx.clone() }
+    fn set_paused(&self, paused: bool) -> bool { self.paused_tx.send_replace(paused) }
+    fn paused(&self) -> bool { *self.paused_tx.borrow() }
+}
+
+/// `sync` 合并窗口：降载期间放宽
+struct Batch {
+    normal: Option<Duration>,
+    shed: Duration,
+    shedding: watch::Receiver<bool>,
+}
+
+impl Batch {
+    fn window(&self) -> Option<Duration> {
+        if *self.shedding.borrow() { Some(self.normal.unwrap_or_default().max(self.shed)) } else { self.normal }
+    }
+}
+
+/// 将人数变化编码为 `SyncFrame` 后分发；暂停期间丢弃变化并计数，恢复时立即推送一次最新人数
+async fn sync_fanout(
+    mut online_rx: watch::Receiver<OnlineStats>,
+    sync_tx: watch::Sender<Arc<SyncFrame>>,
+    mut paused_rx: watch::Receiver<bool>,
+    batch: Batch,
+    clock: SharedClock,
+    codec: SharedCodec,
+    metrics: Arc<Metrics>,
+) {
+    loop {
+        tokio::select! {
+            r = online_rx.changed() => {
+                if r.is_err() { break; }
+                if let Some(window) = batch.window() { tokio::time::sleep(window).await; }
+            }
+            r = paused_rx.changed() => {
+                if r.is_err() { break; }
+                let paused = *paused_rx.borrow_and_update();
+                tracing::info!(paused, "sync fanout {}", if paused { "paused" } else { "resumed" });
+            }
+        }
+        let stats = *online_rx.borrow_and_update();
+        if sync_tx.borrow().stats == stats { continue; }
+        if *paused_rx.borrow() {
+            metrics.add("activenow_sync_suppressed_total", String::new(), 1);
+            continue;
+        }
+        let base = sync_tx.borrow().stats.count;
+        sync_tx.send_replace(Arc::new(SyncFrame::new(stats, base, clock.now_ms(), codec.as_ref())));
+    }
+}
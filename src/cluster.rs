@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::gateway::{AppState, ServiceMode};
+use crate::meta::NodeHeartbeat;
+
+/// 心跳间隔；超过 3 个间隔未更新的节点标为 `stale`
+pub const HEARTBEAT_EVERY: Duration = Duration::from_secs(5);
+
+pub fn version() -> String { env!("CARGO_PKG_VERSION").to_string() }
+
+fn mode_name(mode: &ServiceMode) -> &'static str {
+    match mode {
+        ServiceMode::Normal => "normal",
+        ServiceMode::Maintenance { .. } => "maintenance",
+        ServiceMode::Drain { .. } => "drain",
+    }
+}
+
+/// 本实例当前心跳
+pub fn local_heartbeat(state: &AppState) -> NodeHeartbeat {
+    NodeHeartbeat {
+        node: state.node_id.clone(),
+        version: Some(version()),
+        connections: state.registry.len(),
+        online: state.bus.current().count,
+        mode: Some(mode_name(&state.mode_tx.borrow()).to_string()),
+        via_mirror: false,
+        last_seen_ms: state.clock.now_ms(),
+    }
+}
+
+/// 定期向 MetaStore 登记本实例心跳
+pub fn spawn_heartbeat(state: &AppState) {
+    let st = state.clone();
+    state.tasks.spawn("node_heartbeat", move || {
+        let st = st.clone();
+        async move {
+            let mut tick = tokio::time::interval(HEARTBEAT_EVERY);
+            loop {
+                tick.tick().await;
+                st.meta.heartbeat(local_heartbeat(&st)).await;
+            }
+        }
+    });
+}
+
+#[derive(Debug, Serialize)]
+pub struct NodeView {
+    #[serde(flatten)]
+    pub heartbeat: NodeHeartbeat,
+    /// `alive` / `stale`
+    pub status: &'static str,
+    /// 即本实例
+    pub local: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClusterView {
+    pub node: String,
+    pub nodes: Vec<NodeView>,
+}
+
+/// 集群节点列表；本实例以当前值替代最近一次心跳
+pub async fn view(state: &AppState) -> ClusterView {
+    let now = state.clock.now_ms();
+    let stale_after = 3 * HEARTBEAT_EVERY.as_millis() as u64;
+    let mut nodes: Vec<_> = state.meta.nodes(now).await.into_iter().filter(|n| n.node != state.node_id).collect();
+    nodes.push(local_heartbeat(state));
+    nodes.sort_by(|a, b| a.node.cmp(&b.node));
+    let nodes = nodes
+        .into_iter()
+        .map(|hb| NodeView {
+            status: if now.saturating_sub(hb.last_seen_ms) > stale_after { "stale" } else { "alive" },
+            local: hb.node == state.node_id,
+            heartbeat: hb,
+        })
+        .collect();
+    ClusterView { node: state.node_id.clone(), nodes }
+}
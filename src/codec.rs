@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use axum::extract::ws::Message;
+
+use crate::events::{ClientMsg, OutMsg};
+
+/// 上行帧解码失败
+#[derive(Debug)]
+pub enum DecodeError {
+    /// 不接受此类帧（如 JSON 编解码收到二进制帧）
+    Unsupported,
+    Invalid(String),
+}
+
+/// WS 消息线上格式；默认 `JsonCodec`，嵌入方可替换为站点既有格式（需配套客户端）
+pub trait MessageCodec: Send + Sync {
+    /// 编码下行消息为文本或二进制帧
+    fn encode(&self, msg: &OutMsg) -> Message;
+    /// 解码上行数据帧；`binary` 标识帧类型
+    fn decode(&self, data: &[u8], binary: bool) -> Result<ClientMsg, DecodeError>;
+}
+
+pub type SharedCodec = Arc<dyn MessageCodec>;
+
+/// JSON 文本帧（内置客户端使用的格式）
+pub struct JsonCodec;
+
+impl MessageCodec for JsonCodec {
+    fn encode(&self, msg: &OutMsg) -> Message {
+        Message::Text(msg.encode().into())
+    }
+
+    fn decode(&self, data: &[u8], binary: bool) -> Result<ClientMsg, DecodeError> {
+        if binary { return Err(DecodeError::Unsupported); }
+        serde_json::from_slice(data).map_err(|e| DecodeError::Invalid(e.to_string()))
+    }
+}
@@ -1,19 +1,269 @@
-use std::{collections::HashSet, env, time::Duration};
+use std::{cell::RefCell, collections::HashSet, env, net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr}, time::Duration};
+
+use crate::alerts::AlertConfig;
+use crate::ipfilter::Cidr;
+use crate::mirror::MirrorConfig;
+use crate::shed::ShedConfig;
+use crate::tokens::{self, ApiToken};
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub port: u16,
     pub ping_interval: Option<Duration>,
+    /// 每个连接的首次 Ping 随机推迟 0～该时长，避免大量连接同相位发送（`PING_JITTER`）
+    pub ping_jitter: Duration,
     pub allowed_origins: Option<HashSet<String>>,
+    pub count_mode: CountMode,
+    pub sid_generator: SidGeneratorKind,
+    pub admin_token: Option<String>,
+    /// 预置的 API 令牌（`API_TOKENS`）
+    pub api_tokens: Vec<ApiToken>,
+    /// 统计读取接口是否公开（`PUBLIC_METRICS`）；关闭后需 `read:presence`
+    pub public_metrics: bool,
+    /// 管理接口独立监听地址（`ADMIN_PORT` / `ADMIN_HOST`）；配置后公共端口不再挂载管理接口
+    pub admin_addr: Option<SocketAddr>,
+    pub trust_proxy: bool,
+    pub max_frame_bytes: usize,
+    pub idle_timeout: Option<Duration>,
+    pub alerts: AlertConfig,
+    pub history_interval: Duration,
+    pub history_retention: Duration,
+    /// 各来源进出统计的滑动窗口，亦为 `origin_churn` 事件的间隔（`CHURN_WINDOW`）
+    pub churn_window: Duration,
+    pub origin_quotas: OriginQuotas,
+    pub sync_batch: Option<Duration>,
+    pub resume_secret: Option<String>,
+    pub resume_ttl: Duration,
+    pub event_sink: Option<String>,
+    pub event_sink_buffer: usize,
+    /// 内存保留的最近业务事件条数，0 关闭
+    pub event_history: usize,
+    pub leave_grace: Option<Duration>,
+    pub ip_limits: IpLimits,
+    /// 仅放行的客户端网段（`IP_ALLOWLIST`），为空不限制
+    pub ip_allowlist: Vec<Cidr>,
+    /// 拒绝的客户端网段（`IP_DENYLIST`），优先于允许名单
+    pub ip_denylist: Vec<Cidr>,
+    pub daily_stats_file: Option<String>,
+    pub daily_stats_flush: Duration,
+    /// 日统计换日与汇总对齐所用时区（`STATS_TIMEZONE`）
+    pub stats_timezone: StatsZone,
+    pub bans_file: Option<String>,
+    pub session_secret: Option<String>,
+    /// 导出事件与告警 webhook 的签名密钥
+    pub event_signing_secret: Option<String>,
+    pub update_sid: UpdateSidPolicy,
+    pub max_connections: Option<usize>,
+    pub limit_overflow: bool,
+    pub reconnect: ReconnectLimits,
+    pub hello_fields: HelloFields,
+    pub rollup_schedule: Option<CronSchedule>,
+    pub rollup_retention: Duration,
+    /// 代理注入的国家代码头（`GEO_HEADER`，如 `cf-ipcountry`）；未配置时不统计国家分布
+    pub geo_header: Option<String>,
+    /// `geo` 推送间隔（`GEO_BROADCAST_SECS`）
+    pub geo_interval: Option<Duration>,
+    /// 人数达到该值后对订阅 `delta` 的连接改发增量（`DELTA_THRESHOLD`）
+    pub delta_threshold: Option<usize>,
+    /// 增量模式下的 `sync` 校准间隔（`DELTA_RESYNC_SECS`）
+    pub delta_resync: Duration,
+    /// 允许经管理接口注入虚拟在线成员（`SYNTHETIC_PRESENCE`，仅用于测试 / 预发）
+    pub synthetic_presence: bool,
+    /// 仅统计人数（`PRIVACY_MODE`）：不导出含身份字段的事件，管理接口不返回单个连接的身份
+    pub privacy_mode: bool,
+    pub shed: ShedConfig,
+    /// 实例节点 ID（`NODE_ID`，缺省取 `HOSTNAME`，均无则启动时随机生成）
+    pub node_id: String,
+    /// 镜像模式：向上游实例上报本实例在线统计
+    pub mirror: Option<MirrorConfig>,
+    /// 读取环境变量时发现的格式问题（已按默认值处理）；启动前由 `preflight` 汇总
+    pub problems: Vec<ConfigProblem>,
+}
+
+/// 配置问题；`fatal` 为真时拒绝启动
+#[derive(Debug, Clone)]
+pub struct ConfigProblem {
+    pub fatal: bool,
+    pub key: &'static str,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    pub fn error(key: &'static str, message: impl Into<String>) -> Self { Self { fatal: true, key, message: message.into() } }
+
+    pub fn warning(key: &'static str, message: impl Into<String>) -> Self { Self { fatal: false, key, message: message.into() } }
+}
+
+/// cron 的「分 时」两个字段（按 `STATS_TIMEZONE` 解释）；每个字段支持 `*`、`*/N`、`a,b,c` 与单个数字，
+/// 另有别名 `@hourly`（`0 *`）、`@daily`（`0 0`）；只写一个字段时小时为 `*`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+}
+
+impl CronSchedule {
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = match raw.trim() {
+            "@hourly" => "0 *",
+            "@daily" => "0 0",
+            s => s,
+        };
+        fn field(f: &str, max: u32) -> Option<Vec<u32>> {
+            let mut v: Vec<u32> = if f == "*" {
+                (0..max).collect()
+            } else if let Some(step) = f.strip_prefix("*/") {
+                let step: u32 = step.parse().ok().filter(|n| *n > 0)?;
+                (0..max).step_by(step as usize).collect()
+            } else {
+                f.split(',').map(|n| n.trim().parse().ok().filter(|n| *n < max)).collect::<Option<_>>()?
+            };
+            v.sort_unstable();
+            v.dedup();
+            Some(v)
+        }
+        let mut parts = raw.split_whitespace();
+        let minutes = field(parts.next()?, 60)?;
+        let hours = field(parts.next().unwrap_or("*"), 24)?;
+        if parts.next().is_some() { return None; }
+        Some(Self { minutes, hours })
+    }
+
+    /// `now_ms` 之后的下一个触发时刻（整分钟，Unix 毫秒）；夏令时跳过的本地时刻不触发，重复的本地时刻触发两次
+    pub fn next_after(&self, now_ms: u64, zone: &StatsZone) -> u64 {
+        let mut t = now_ms / 60_000 * 60_000 + 60_000;
+        // 最多向后查找两天
+        for _ in 0..2 * 24 * 60 {
+            let local = t as i64 + zone.offset_at(t) * 1000;
+            let minute = (local / 60_000).rem_euclid(60) as u32;
+            let hour = (local / 3_600_000).rem_euclid(24) as u32;
+            if self.minutes.contains(&minute) && self.hours.contains(&hour) { return t; }
+            t += 60_000;
+        }
+        now_ms + 3_600_000
+    }
+}
+
+/// 按来源的并发连接上限；规则语法同 `ALLOWED_ORIGINS`，按配置顺序首条匹配
+#[derive(Debug, Clone, Default)]
+pub struct OriginQuotas {
+    pub rules: Vec<(String, usize)>,
+    pub default_max: Option<usize>,
+}
+
+/// 按客户端网段的并发连接上限；IPv4 / IPv6 分别按前缀长度聚合
+#[derive(Debug, Clone)]
+pub struct IpLimits {
+    pub max_connections: Option<usize>,
+    pub v4_prefix: u8,
+    pub v6_prefix: u8,
+}
+
+impl IpLimits {
+    /// 聚合键，如 `203.0.113.0/24`、`2001:db8:1:2::/64`；无法解析的地址原样返回
+    pub fn subnet(&self, ip: &str) -> String {
+        match ip.parse::<IpAddr>() {
+            Ok(IpAddr::V4(v4)) => {
+                let bits = u32::from(v4) & (u32::MAX.checked_shl(32 - self.v4_prefix as u32).unwrap_or(0));
+                format!("{}/{}", Ipv4Addr::from(bits), self.v4_prefix)
+            }
+            Ok(IpAddr::V6(v6)) => match v6.to_ipv4_mapped() {
+                Some(v4) => self.subnet(&v4.to_string()),
+                None => {
+                    let bits = u128::from(v6) & (u128::MAX.checked_shl(128 - self.v6_prefix as u32).unwrap_or(0));
+                    format!("{}/{}", Ipv6Addr::from(bits), self.v6_prefix)
+                }
+            },
+            Err(_) => ip.to_string(),
+        }
+    }
+}
+
+/// `hello` 附加字段（`HELLO_FIELDS`），供客户端按握手结果自行配置
+#[derive(Debug, Clone, Default)]
+pub struct HelloFields {
+    pub version: bool,
+    /// 列出 `region` 且配置了 `REGION` 时下发
+    pub region: Option<String>,
+    pub features: bool,
+}
+
+/// 高频重连检测：按网段 / 会话统计每分钟握手次数
+#[derive(Debug, Clone, Default)]
+pub struct ReconnectLimits {
+    pub max_per_minute: Option<u32>,
+    /// 违规达到该次数后临时封禁
+    pub ban_after: Option<u32>,
+    pub ban_duration: Duration,
+}
+
+/// 连接 `sid` 生成方式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SidGeneratorKind {
+    Nanoid { alphabet: Option<String>, len: usize },
+    UuidV7,
+    Snowflake { node_id: u16 },
+}
+
+/// 在线人数计数口径
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountMode {
+    /// 按 session_id 去重（同一用户多标签页计 1）
+    Session,
+    /// 按连接计数（每个标签页各计 1）
+    Connection,
+    /// 按访客计数：关联了用户的会话按用户合并，其余按会话
+    User,
+}
+
+/// 连接建立后 `updateSid` 的处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateSidPolicy {
+    /// 始终允许
+    Allow,
+    /// 仅允许在尚未携带会话 ID 时设置一次
+    Once,
+    /// 一律拒绝
+    Deny,
 }
 
 impl Config {
+    /// 无法解析的取值按默认值处理并记入 `problems`
     pub fn from_env() -> Self {
-        fn read_u64(key: &str, default: u64) -> u64 {
-            env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
-        }
-        let port = env::var("PORT").ok().and_then(|v| v.parse::<u16>().ok()).unwrap_or(8080);
-        let ping_secs = read_u64("PING_INTERVAL", 0);
+        let problems = RefCell::new(Vec::new());
+        let invalid = |key: &'static str, raw: &str, expected: &str| {
+            problems.borrow_mut().push(ConfigProblem::error(key, format!("invalid value {raw:?}, expected {expected}")));
+        };
+        let read_raw = |key: &str| env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let read_u64 = |key: &'static str, default: u64| -> u64 {
+            read_raw(key).map_or(default, |v| v.trim().parse().unwrap_or_else(|_| { invalid(key, &v, "a non-negative integer"); default }))
+        };
+        // 超过上限视为无效取值
+        let read_u64_max = |key: &'static str, default: u64, max: u64| -> u64 {
+            let v = read_u64(key, default);
+            if v > max { invalid(key, &v.to_string(), &format!("an integer up to {max}")); return default; }
+            v
+        };
+        // 时长：`500ms`、`2s`、`1m30s` 等，纯数字按秒
+        let read_duration = |key: &'static str, default: Duration| -> Duration {
+            read_raw(key).map_or(default, |v| parse_duration(&v).unwrap_or_else(|| { invalid(key, &v, "a duration like 500ms, 30s, 1m30s"); default }))
+        };
+        let read_bool = |key: &'static str, default: bool| -> bool {
+            read_raw(key).map_or(default, |v| match v.trim().to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => true,
+                "0" | "false" | "no" | "off" => false,
+                _ => { invalid(key, &v, "true or false"); default }
+            })
+        };
+        let read_choice = |key: &'static str, choices: &[&str]| -> String {
+            let v = read_raw(key).unwrap_or_default().trim().to_ascii_lowercase();
+            if !v.is_empty() && !choices.contains(&v.as_str()) { invalid(key, &v, &format!("one of {}", choices.join(" / "))); }
+            v
+        };
+        let port = match read_raw("PORT") {
+            Some(v) => v.trim().parse::<u16>().unwrap_or_else(|_| { invalid("PORT", &v, "a port number"); 8080 }),
+            None => 8080,
+        };
         let allowed_origins = {
             let raw = env::var("ALLOWED_ORIGINS").unwrap_or_default();
             let items: Vec<_> = raw
@@ -24,10 +274,285 @@ impl Config {
                 .collect();
             if items.is_empty() { None } else { Some(items.into_iter().collect()) }
         };
-        Self {
+        let count_mode = match read_choice("COUNT_MODE", &["session", "connection", "conn", "user", "visitor"]).as_str() {
+            "connection" | "conn" => CountMode::Connection,
+            "user" | "visitor" => CountMode::User,
+            _ => CountMode::Session,
+        };
+        let sid_generator = match read_choice("SID_GENERATOR", &["nanoid", "uuidv7", "uuid", "snowflake"]).as_str() {
+            "uuidv7" | "uuid" => SidGeneratorKind::UuidV7,
+            "snowflake" => SidGeneratorKind::Snowflake { node_id: read_u64("SID_NODE_ID", 0).min(1023) as u16 },
+            _ => SidGeneratorKind::Nanoid {
+                alphabet: env::var("SID_ALPHABET").ok().filter(|s| !s.is_empty()),
+                len: read_u64("SID_LENGTH", 21) as usize,
+            },
+        };
+        let admin_token = env::var("ADMIN_TOKEN").ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let trust_proxy = read_bool("TRUST_PROXY", false);
+        let alerts = AlertConfig {
+            thresholds: {
+                let mut v: Vec<usize> = env::var("ALERT_THRESHOLDS")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .filter_map(|s| s.parse().map_err(|_| invalid("ALERT_THRESHOLDS", s, "comma-separated integers")).ok())
+                    .filter(|n| *n > 0)
+                    .collect();
+                v.sort_unstable();
+                v.dedup();
+                v
+            },
+            zero_after: Some(read_u64_max("ALERT_ZERO_MINUTES", 0, MAX_DURATION.as_secs() / 60)).filter(|m| *m > 0).map(|m| Duration::from_secs(m * 60)),
+            cooldown: Duration::from_secs(read_u64("ALERT_COOLDOWN_SECS", 600)),
+            webhook: env::var("ALERT_WEBHOOK").ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+            signing_secret: env::var("EVENT_SIGNING_SECRET").ok().filter(|s| !s.is_empty()),
+        };
+        let origin_quotas = OriginQuotas {
+            rules: env::var("ORIGIN_QUOTAS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .filter_map(|item| {
+                    let parsed = item.rsplit_once('=').and_then(|(e, n)| Some((e.trim().to_ascii_lowercase(), n.trim().parse().ok()?)));
+                    if parsed.is_none() { invalid("ORIGIN_QUOTAS", item, "origin=max"); }
+                    parsed
+                })
+                .collect(),
+            default_max: Some(read_u64("ORIGIN_MAX_CONNECTIONS", 0) as usize).filter(|n| *n > 0),
+        };
+        let api_tokens = {
+            let raw = env::var("API_TOKENS").unwrap_or_default();
+            let parsed = tokens::parse_spec(&raw);
+            let items = raw.split(',').filter(|s| !s.trim().is_empty()).count();
+            if parsed.len() != items {
+                problems.borrow_mut().push(ConfigProblem::error("API_TOKENS", format!("{} of {items} entries malformed, expected name=scope+scope=token", items - parsed.len())));
+            }
+            parsed
+        };
+        let read_cidrs = |key: &'static str| -> Vec<Cidr> {
+            let raw = env::var(key).unwrap_or_default();
+            raw.split(',').map(str::trim).filter(|s| !s.is_empty()).filter_map(|s| {
+                let cidr = Cidr::parse(s);
+                if cidr.is_none() { invalid(key, s, "an IP address or CIDR like 10.0.0.0/8"); }
+                cidr
+            }).collect()
+        };
+        let node_id = read_raw("NODE_ID").or_else(|| read_raw("HOSTNAME")).map(|s| s.trim().chars().take(64).collect()).unwrap_or_else(|| {
+            let alphabet: Vec<char> = "abcdefghijklmnopqrstuvwxyz0123456789".chars().collect();
+            format!("node-{}", nanoid::nanoid!(8, &alphabet))
+        });
+        let mut cfg = Self {
             port,
-            ping_interval: if ping_secs > 0 { Some(Duration::from_secs(ping_secs)) } else { None },
+            ping_interval: Some(read_duration("PING_INTERVAL", Duration::ZERO)).filter(|d| !d.is_zero()),
+            ping_jitter: read_duration("PING_JITTER", Duration::ZERO),
             allowed_origins,
+            count_mode,
+            sid_generator,
+            admin_token,
+            api_tokens,
+            public_metrics: read_bool("PUBLIC_METRICS", true),
+            admin_addr: Some(read_u64("ADMIN_PORT", 0)).filter(|p| *p > 0).map(|p| {
+                if p > u16::MAX as u64 { invalid("ADMIN_PORT", &p.to_string(), "a port number"); }
+                let host = read_raw("ADMIN_HOST").and_then(|h| h.trim().parse::<IpAddr>().map_err(|_| invalid("ADMIN_HOST", &h, "an IP address")).ok());
+                SocketAddr::new(host.unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST)), p as u16)
+            }),
+            trust_proxy,
+            max_frame_bytes: read_u64("MAX_FRAME_BYTES", 4096).max(64) as usize,
+            idle_timeout: Some(read_duration("IDLE_TIMEOUT", Duration::ZERO)).filter(|d| !d.is_zero()),
+            alerts,
+            history_interval: read_duration("HISTORY_INTERVAL", Duration::from_secs(10)).max(Duration::from_secs(1)),
+            history_retention: read_duration("HISTORY_RETENTION", Duration::from_secs(86_400)).max(Duration::from_secs(60)),
+            churn_window: read_duration("CHURN_WINDOW", Duration::from_secs(60)),
+            origin_quotas,
+            sync_batch: Some(read_u64("SYNC_BATCH_MS", 0)).filter(|ms| *ms > 0).map(Duration::from_millis),
+            resume_secret: env::var("RESUME_SECRET").ok().filter(|s| !s.is_empty()),
+            resume_ttl: read_duration("RESUME_TTL", Duration::from_secs(300)),
+            event_sink: env::var("EVENT_SINK").ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+            event_sink_buffer: read_u64("EVENT_SINK_BUFFER", 10_000).max(1) as usize,
+            event_history: read_u64("EVENT_HISTORY", 0).min(100_000) as usize,
+            leave_grace: Some(read_u64("LEAVE_GRACE_MS", 0)).filter(|ms| *ms > 0).map(Duration::from_millis),
+            session_secret: env::var("SESSION_SECRET").ok().filter(|s| !s.is_empty()),
+            event_signing_secret: env::var("EVENT_SIGNING_SECRET").ok().filter(|s| !s.is_empty()),
+            update_sid: match read_choice("UPDATE_SID", &["allow", "once", "deny", "off"]).as_str() {
+                "once" => UpdateSidPolicy::Once,
+                "deny" | "off" => UpdateSidPolicy::Deny,
+                _ => UpdateSidPolicy::Allow,
+            },
+            geo_header: env::var("GEO_HEADER").ok().map(|s| s.trim().to_ascii_lowercase()).filter(|s| !s.is_empty()),
+            geo_interval: Some(read_u64("GEO_BROADCAST_SECS", 30)).filter(|s| *s > 0).map(Duration::from_secs),
+            delta_threshold: Some(read_u64("DELTA_THRESHOLD", 0) as usize).filter(|n| *n > 0),
+            delta_resync: Duration::from_secs(read_u64("DELTA_RESYNC_SECS", 30).max(1)),
+            shed: ShedConfig {
+                rss_mb: Some(read_u64("SHED_RSS_MB", 0)).filter(|v| *v > 0),
+                lag: Some(read_u64("SHED_LAG_MS", 0)).filter(|v| *v > 0).map(Duration::from_millis),
+                queue_depth: Some(read_u64("SHED_QUEUE_DEPTH", 0)).filter(|v| *v > 0).map(|v| v as usize),
+                sync_batch: Duration::from_millis(read_u64("SHED_SYNC_BATCH_MS", 2000)),
+            },
+            mirror: env::var("MIRROR_UPSTREAM").ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).map(|upstream| MirrorConfig {
+                upstream,
+                node: read_raw("MIRROR_NODE").map(|s| s.trim().to_string()).unwrap_or_else(|| node_id.clone()),
+                token: env::var("MIRROR_TOKEN").ok().filter(|s| !s.is_empty()),
+            }),
+            synthetic_presence: read_bool("SYNTHETIC_PRESENCE", false),
+            privacy_mode: read_bool("PRIVACY_MODE", false),
+            hello_fields: {
+                let raw = env::var("HELLO_FIELDS").unwrap_or_default().to_ascii_lowercase();
+                let on: HashSet<&str> = raw.split(',').map(str::trim).collect();
+                HelloFields {
+                    version: on.contains("version"),
+                    region: on.contains("region").then(|| env::var("REGION").ok()).flatten().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+                    features: on.contains("features"),
+                }
+            },
+            reconnect: ReconnectLimits {
+                max_per_minute: Some(read_u64("RECONNECT_MAX_PER_MINUTE", 0) as u32).filter(|n| *n > 0),
+                ban_after: Some(read_u64("RECONNECT_BAN_AFTER", 0) as u32).filter(|n| *n > 0),
+                ban_duration: Duration::from_secs(read_u64_max("RECONNECT_BAN_SECS", 3600, MAX_DURATION.as_secs()).max(1)),
+            },
+            rollup_schedule: match env::var("ROLLUP_SCHEDULE").unwrap_or_default().trim() {
+                "" => CronSchedule::parse("@hourly"),
+                "off" | "none" => None,
+                raw => CronSchedule::parse(raw).or_else(|| {
+                    invalid("ROLLUP_SCHEDULE", raw, "cron minute/hour fields, @hourly, @daily or off");
+                    CronSchedule::parse("@hourly")
+                }),
+            },
+            rollup_retention: Duration::from_secs(read_u64_max("ROLLUP_RETENTION_DAYS", 90, 3650).max(1) * 86_400),
+            max_connections: Some(read_u64("MAX_CONNECTIONS", 0) as usize).filter(|n| *n > 0),
+            limit_overflow: read_bool("LIMIT_OVERFLOW", false),
+            bans_file: env::var("BANS_FILE").ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+            daily_stats_file: env::var("DAILY_STATS_FILE").ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+            stats_timezone: match env::var("STATS_TIMEZONE") {
+                Ok(raw) if !raw.trim().is_empty() => StatsZone::parse(&raw).unwrap_or_else(|| {
+                    invalid("STATS_TIMEZONE", &raw, "UTC, a UTC offset like +08:00 or an IANA name like Asia/Shanghai");
+                    StatsZone::default()
+                }),
+                _ => StatsZone::default(),
+            },
+            daily_stats_flush: Duration::from_secs(read_u64("DAILY_STATS_FLUSH_SECS", 10).max(1)),
+            ip_limits: IpLimits {
+                max_connections: Some(read_u64("IP_MAX_CONNECTIONS", 0) as usize).filter(|n| *n > 0),
+                v4_prefix: read_u64("IP_V4_PREFIX", 32).clamp(1, 32) as u8,
+                v6_prefix: read_u64("IP_V6_PREFIX", 64).clamp(1, 128) as u8,
+            },
+            ip_allowlist: read_cidrs("IP_ALLOWLIST"),
+            ip_denylist: read_cidrs("IP_DENYLIST"),
+            node_id,
+            problems: Vec::new(),
+        };
+        cfg.problems = problems.into_inner();
+        cfg
+    }
+}
+
+/// 解析 UTC 偏移（秒）：`UTC` / `Z`、`+08:00`、`-0530`、`+8`；IANA 时区名见 `StatsZone`
+pub fn parse_utc_offset(raw: &str) -> Option<i64> {
+    let raw = raw.trim();
+    if raw.eq_ignore_ascii_case("utc") || raw.eq_ignore_ascii_case("z") { return Some(0); }
+    let raw = raw.strip_prefix("UTC").or_else(|| raw.strip_prefix("GMT")).unwrap_or(raw);
+    let (sign, rest) = match raw.as_bytes().first()? {
+        b'+' => (1, &raw[1..]),
+        b'-' => (-1, &raw[1..]),
+        _ => return None,
+    };
+    let (h, m) = match rest.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    let (h, m): (i64, i64) = (h.parse().ok()?, m.parse().ok()?);
+    if h > 14 || m >= 60 { return None; }
+    Some(sign * (h * 3600 + m * 60))
+}
+
+/// 统计时区：固定 UTC 偏移，或按夏令时变化偏移的 IANA 时区
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsZone {
+    /// 偏移秒数
+    Fixed(i64),
+    Named(chrono_tz::Tz),
+}
+
+impl Default for StatsZone {
+    fn default() -> Self { Self::Fixed(0) }
+}
+
+impl StatsZone {
+    /// 先按固定偏移解析，再按 IANA 时区名（如 `Asia/Shanghai`、`America/New_York`）
+    pub fn parse(raw: &str) -> Option<Self> {
+        parse_utc_offset(raw).map(Self::Fixed).or_else(|| raw.trim().parse().ok().map(Self::Named))
+    }
+
+    /// `ts_ms` 时刻相对 UTC 的偏移（秒）
+    pub fn offset_at(&self, ts_ms: u64) -> i64 {
+        use chrono::{Offset, TimeZone};
+        match self {
+            Self::Fixed(secs) => *secs,
+            Self::Named(tz) => match chrono::DateTime::from_timestamp_millis(ts_ms as i64) {
+                Some(t) => tz.offset_from_utc_datetime(&t.naive_utc()).fix().local_minus_utc() as i64,
+                None => 0,
+            },
+        }
+    }
+
+    /// `ts_ms` 所在本地日的零点（Unix 毫秒）；零点因夏令时不存在时取当日最早的本地时刻
+    pub fn day_start(&self, ts_ms: u64) -> u64 {
+        const DAY_MS: i64 = 86_400_000;
+        let offset_ms = self.offset_at(ts_ms) * 1000;
+        let midnight = (ts_ms as i64 + offset_ms).div_euclid(DAY_MS) * DAY_MS;
+        let start = match self {
+            Self::Fixed(_) => midnight - offset_ms,
+            Self::Named(tz) => {
+                use chrono::TimeZone;
+                let local = chrono::DateTime::from_timestamp_millis(midnight).map(|t| t.naive_utc());
+                // 零点落在跳过的时段内时，逐小时向后找第一个存在的本地时刻
+                (0..24i64)
+                    .find_map(|h| tz.from_local_datetime(&(local? + chrono::Duration::hours(h))).earliest())
+                    .map_or(midnight - offset_ms, |t| t.timestamp_millis())
+            }
+        };
+        start.max(0) as u64
+    }
+}
+
+impl std::fmt::Display for StatsZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fixed(0) => f.write_str("UTC"),
+            Self::Fixed(secs) => write!(f, "{}{:02}:{:02}", if *secs < 0 { '-' } else { '+' }, secs.abs() / 3600, secs.abs() % 3600 / 60),
+            Self::Named(tz) => f.write_str(tz.name()),
         }
     }
 }
+
+/// `parse_duration` 结果上限；查询参数也经此解析，避免超大值参与时间运算
+pub const MAX_DURATION: Duration = Duration::from_secs(365 * 86_400);
+
+/// 解析时长：`500ms`、`90s`、`30m`、`1h`、`1d`，可组合如 `1m30s`；纯数字按秒。溢出返回 `None`，结果不超过 `MAX_DURATION`
+pub fn parse_duration(raw: &str) -> Option<Duration> {
+    let raw = raw.trim();
+    if raw.is_empty() { return None; }
+    if let Ok(secs) = raw.parse::<u64>() { return Some(Duration::from_secs(secs).min(MAX_DURATION)); }
+    let mut total = Duration::ZERO;
+    let mut rest = raw;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if digits == 0 { return None; }
+        let n: u64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit_len = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "ms" => Duration::from_millis(n),
+            "s" => Duration::from_secs(n),
+            "m" => Duration::from_secs(n.checked_mul(60)?),
+            "h" => Duration::from_secs(n.checked_mul(3600)?),
+            "d" => Duration::from_secs(n.checked_mul(86_400)?),
+            _ => return None,
+        };
+        total = total.checked_add(unit)?;
+        rest = &rest[unit_len..];
+    }
+    Some(total.min(MAX_DURATION))
+}
@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use axum::extract::ws::Message;
+use serde::Serialize;
+
+pub use activenow_protocol::{ClientMsg, CloseReason, Geo};
+
+use crate::codec::MessageCodec;
+use crate::gateway::OnlineStats;
+
+/// 支持的协议版本；未指定时按 v1 处理
+pub const MIN_VERSION: u8 = 1;
+pub const MAX_VERSION: u8 = 2;
+
+/// 子协议名，如 `activenow.v2`（服务端优先选择高版本）
+pub const SUBPROTOCOLS: [&str; 2] = ["activenow.v2", "activenow.v1"];
+
+/// 协商版本：优先查询参数 `v`，其次子协议；超出范围时夹到支持区间
+pub fn negotiate(query_v: Option<u8>, subprotocol: Option<&str>) -> u8 {
+    let from_proto = subprotocol.and_then(|p| p.strip_prefix("activenow.v")).and_then(|v| v.parse().ok());
+    query_v.or(from_proto).unwrap_or(MIN_VERSION).clamp(MIN_VERSION, MAX_VERSION)
+}
+
+/// 下行消息（服务端借用编码；客户端解码类型见 `activenow_protocol::ServerMsg`，新增字段需同步）
+///
+/// - v1：`hello{sid,count,v}`、`sync{count}`
+/// - v2：`hello`/`sync` 额外携带原始连接数 `connections` 与去重会话数 `sessions`，`sync` 另带服务端时间戳 `ts`（毫秒）
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum OutMsg<'a> {
+    Sync {
+        count: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        connections: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        sessions: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ts: Option<u64>,
+    },
+    Hello {
+        sid: &'a str,
+        count: usize,
+        v: u8,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        connections: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        sessions: Option<usize>,
+        /// 断线重连令牌，重连时以 `?resume=` 携带可找回原 `sid`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        resume_token: Option<String>,
+        /// 本次连接是否由重连令牌恢复
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        resumed: bool,
+        /// 配置 `SESSION_SECRET` 时下发的签名会话 ID，客户端应持久化并在后续连接携带
+        #[serde(skip_serializing_if = "Option::is_none")]
+        session_id: Option<String>,
+        /// 溢出连接：已计入在线，但后续不推送 `sync`
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        overflow: bool,
+        /// 以下由 `HELLO_FIELDS` 开启：服务端版本、部署区域、本连接可用的协议能力
+        #[serde(skip_serializing_if = "Option::is_none")]
+        server_version: Option<&'static str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        region: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        features: Option<Vec<&'static str>>,
+    },
+    /// 人数增量（`?events=delta` 且人数达到 `DELTA_THRESHOLD`），客户端在已知人数上累加
+    Delta { d: i64 },
+    /// 按国家的在线分布（`?events=geo`）
+    Geo(&'a Geo),
+    /// 服务端主动断开前的通知，随后发送对应关闭码的 Close 帧
+    Closing {
+        reason: CloseReason,
+        /// 建议的重连等待；`None` 表示不应自动重连
+        retry_after_ms: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<&'a str>,
+    },
+    /// 上行消息被拒绝：`too_large` / `binary_unsupported` / `invalid_message`
+    Error {
+        code: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+}
+
+impl<'a> OutMsg<'a> {
+    pub fn hello(v: u8, sid: &'a str, stats: &OnlineStats) -> Self {
+        let v2 = v >= 2;
+        OutMsg::Hello {
+            sid,
+            count: stats.count,
+            v,
+            connections: v2.then_some(stats.connections),
+            sessions: v2.then_some(stats.sessions),
+            resume_token: None,
+            resumed: false,
+            session_id: None,
+            overflow: false,
+            server_version: None,
+            region: None,
+            features: None,
+        }
+    }
+
+    pub fn with_resume(mut self, token: Option<String>, was_resumed: bool) -> Self {
+        if let OutMsg::Hello { resume_token, resumed, .. } = &mut self {
+            *resume_token = token;
+            *resumed = was_resumed;
+        }
+        self
+    }
+
+    pub fn with_session(mut self, signed: Option<String>) -> Self {
+        if let OutMsg::Hello { session_id, .. } = &mut self {
+            *session_id = signed;
+        }
+        self
+    }
+
+    pub fn with_overflow(mut self, on: bool) -> Self {
+        if let OutMsg::Hello { overflow, .. } = &mut self {
+            *overflow = on;
+        }
+        self
+    }
+
+    pub fn with_server_info(mut self, version: Option<&'static str>, label: Option<&'a str>, caps: Option<Vec<&'static str>>) -> Self {
+        if let OutMsg::Hello { server_version, region, features, .. } = &mut self {
+            *server_version = version;
+            *region = label;
+            *features = caps;
+        }
+        self
+    }
+
+    pub fn sync(v: u8, stats: &OnlineStats, now_ms: u64) -> Self {
+        let v2 = v >= 2;
+        OutMsg::Sync {
+            count: stats.count,
+            connections: v2.then_some(stats.connections),
+            sessions: v2.then_some(stats.sessions),
+            ts: v2.then_some(now_ms),
+        }
+    }
+
+    pub fn error(code: &'a str, message: Option<String>) -> Self { OutMsg::Error { code, message } }
+
+    pub fn encode(&self) -> String { serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string()) }
+}
+
+/// 按连接订阅的下行事件（`?events=sync,error,geo,delta`）；`hello` / `closing` 总是下发
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventFilter {
+    pub sync: bool,
+    pub error: bool,
+    /// 以下需显式订阅，未指定 `events` 时不推送
+    pub geo: bool,
+    /// 客户端能处理 `delta`，大人数时以增量代替 `sync`
+    pub delta: bool,
+}
+
+impl Default for EventFilter {
+    fn default() -> Self { Self { sync: true, error: true, geo: false, delta: false } }
+}
+
+impl EventFilter {
+    /// 逗号分隔、不区分大小写；未知名称忽略，未指定时订阅 `sync` 与 `error`
+    pub fn parse(raw: Option<&str>) -> Self {
+        let Some(raw) = raw else { return Self::default() };
+        let mut f = Self { sync: false, error: false, geo: false, delta: false };
+        for name in raw.split(',').map(|s| s.trim().to_ascii_lowercase()) {
+            match name.as_str() {
+                "sync" => f.sync = true,
+                "error" => f.error = true,
+                "geo" => f.geo = true,
+                "delta" => f.delta = true,
+                _ => {}
+            }
+        }
+        f
+    }
+}
+
+/// 按连接的人数推送节流（`?min_change=X&min_interval=Y`）：变化超过 `X` 立即推送，否则距上次推送满 `Y` 后推送；
+/// 只配 `X` 时较小的变化累积超过 `X` 才推送
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncThrottle {
+    pub min_change: Option<usize>,
+    pub min_interval: Option<Duration>,
+}
+
+/// `min_interval` 上限
+const MAX_THROTTLE_INTERVAL: Duration = Duration::from_secs(600);
+
+/// 节流判定结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleDecision {
+    Send,
+    /// 人数与上次推送相同
+    Skip,
+    /// 暂缓；`Some` 为距可推送的剩余时长，`None` 为等待更大的变化
+    Wait(Option<Duration>),
+}
+
+impl SyncThrottle {
+    /// 两者均未配置（或 `min_change=0` 且无间隔）时不节流；`min_interval` 语法同时长配置
+    pub fn parse(min_change: Option<usize>, min_interval: Option<&str>) -> Option<Self> {
+        let min_interval = min_interval.and_then(crate::config::parse_duration).filter(|d| !d.is_zero()).map(|d| d.min(MAX_THROTTLE_INTERVAL));
+        let min_change = min_change.filter(|n| *n > 0 || min_interval.is_some());
+        (min_change.is_some() || min_interval.is_some()).then_some(Self { min_change, min_interval })
+    }
+
+    /// `sent` 为上次推送的人数，`elapsed` 为距上次推送的时长
+    pub fn decide(&self, sent: usize, current: usize, elapsed: Duration) -> ThrottleDecision {
+        let diff = sent.abs_diff(current);
+        if diff == 0 { return ThrottleDecision::Skip; }
+        if self.min_change.is_some_and(|x| diff > x) { return ThrottleDecision::Send; }
+        match self.min_interval {
+            Some(y) if elapsed >= y => ThrottleDecision::Send,
+            Some(y) => ThrottleDecision::Wait(Some(y - elapsed)),
+            None => ThrottleDecision::Wait(None),
+        }
+    }
+}
+
+/// 预序列化的 `sync` 帧：每次人数变化只编码一次，各连接按版本共享同一份字节
+#[derive(Debug, Clone)]
+pub struct SyncFrame {
+    pub stats: OnlineStats,
+    /// 上一帧的人数；连接已知人数等于它时才可改发 `delta`
+    pub base: usize,
+    v1: Message,
+    v2: Message,
+    delta: Message,
+}
+
+impl SyncFrame {
+    pub fn new(stats: OnlineStats, base: usize, now_ms: u64, codec: &dyn MessageCodec) -> Self {
+        Self {
+            stats,
+            base,
+            v1: codec.encode(&OutMsg::sync(1, &stats, now_ms)),
+            v2: codec.encode(&OutMsg::sync(2, &stats, now_ms)),
+            delta: codec.encode(&OutMsg::Delta { d: stats.count as i64 - base as i64 }),
+        }
+    }
+
+    pub fn payload(&self, v: u8) -> Message {
+        if v >= 2 { self.v2.clone() } else { self.v1.clone() }
+    }
+
+    pub fn delta(&self) -> Message { self.delta.clone() }
+}
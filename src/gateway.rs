@@ -1,36 +1,174 @@
-use std::{collections::HashSet, time::Duration};
+use std::{collections::HashSet, net::SocketAddr, time::Duration};
 
-use axum::{extract::{Query, State, ws::{WebSocket, WebSocketUpgrade, Message}}, response::IntoResponse, http::HeaderMap};
-use futures_util::{StreamExt, SinkExt};
+use axum::{extract::{ConnectInfo, MatchedPath, Query, State, ws::{close_code, CloseFrame, WebSocket, WebSocketUpgrade, Message}}, response::IntoResponse, http::HeaderMap};
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 
-use tokio::sync::watch;
-use std::time::{SystemTime, UNIX_EPOCH};
-use crate::id::new_sid;
-use crate::meta::MetaStore;
+use dashmap::DashMap;
+use tokio::{sync::watch, task::AbortHandle};
+use crate::auth::AuthProvider;
+use crate::bus::EventBus;
+use crate::config::{CountMode, HelloFields, IpLimits, OriginQuotas, UpdateSidPolicy};
+use crate::codec::{DecodeError, MessageCodec, SharedCodec};
+use crate::events::{self, CloseReason, EventFilter, ClientMsg, OutMsg, SyncThrottle, ThrottleDecision};
+use crate::geo;
+use crate::history::OnlineHistory;
+use crate::logging::LogControl;
+use crate::id::IdGenerator;
+use crate::meta::{Attribution, Ban, BanTarget, MetaStore};
+use crate::reconnect::{ReconnectGuard, Verdict};
+use crate::registry::{ConnInfo, ConnRegistry};
+use crate::resume::ResumeKeys;
+use crate::sink::{EventPipeline, LeaveReason, SinkEvent};
+use crate::supervisor::Supervisor;
+use crate::shed::LoadShedder;
+use crate::ipfilter::IpFilter;
+use crate::mirror::{self, MirrorNode};
+use crate::synthetic::SyntheticLoad;
+use crate::tokens::{ApiTokens, Scope};
+use crate::metrics::Metrics;
+use crate::outbox::{Outbox, Priority};
+use crate::time::SharedClock;
 
 #[derive(Clone)]
 /// 全局共享应用状态（仅在线人数）
 pub struct AppState {
     pub ping_interval: Option<Duration>,
+    pub ping_jitter: Duration,
     pub meta: std::sync::Arc<dyn MetaStore>,
-    pub online_tx: watch::Sender<usize>,
-    pub online_rx: watch::Receiver<usize>,
+    /// 在线统计与 `sync` 帧的发布 / 订阅
+    pub bus: std::sync::Arc<dyn EventBus>,
     pub origin_whitelist: Option<HashSet<String>>,
+    pub count_mode: CountMode,
+    pub ids: std::sync::Arc<dyn IdGenerator>,
+    pub registry: std::sync::Arc<ConnRegistry>,
+    pub admin_token: Option<String>,
+    /// 带权限的 API 令牌（`API_TOKENS` 与管理接口签发）
+    pub tokens: std::sync::Arc<ApiTokens>,
+    pub trust_proxy: bool,
+    /// `PRIVACY_MODE`：只对外提供聚合人数
+    pub privacy_mode: bool,
+    pub mode_tx: watch::Sender<ServiceMode>,
+    pub max_frame_bytes: usize,
+    pub idle_timeout: Option<Duration>,
+    pub history: std::sync::Arc<OnlineHistory>,
+    pub auth: std::sync::Arc<dyn AuthProvider>,
+    pub origin_quotas: OriginQuotas,
+    pub resume: Option<std::sync::Arc<ResumeKeys>>,
+    /// 业务事件导出（`EVENT_SINK`）
+    pub sink: Option<EventPipeline>,
+    /// 离开宽限期（`LEAVE_GRACE_MS`）；期内同一 session 重连则不计离开
+    pub leave_grace: Option<Duration>,
+    /// session_id -> (待清理的 sid, 延迟任务)
+    pub pending_leaves: std::sync::Arc<DashMap<String, (String, AbortHandle)>>,
+    pub ip_limits: IpLimits,
+    pub clock: SharedClock,
+    pub metrics: std::sync::Arc<Metrics>,
+    /// 会话 ID 签名密钥（`SESSION_SECRET`）；配置后仅接受签名有效的会话 ID
+    pub session_secret: Option<String>,
+    pub update_sid: UpdateSidPolicy,
+    /// 全局订阅连接上限（`MAX_CONNECTIONS`，不含溢出连接）
+    pub max_connections: Option<usize>,
+    /// 超出 `MAX_CONNECTIONS` / 来源配额时以溢出模式接入而非 `429`
+    pub limit_overflow: bool,
+    /// WS 消息编解码，默认 JSON
+    pub codec: SharedCodec,
+    pub hello_fields: HelloFields,
+    /// 高频重连检测（`RECONNECT_MAX_PER_MINUTE`）
+    pub reconnects: std::sync::Arc<ReconnectGuard>,
+    /// 国家代码头（`GEO_HEADER`）
+    pub geo_header: Option<String>,
+    /// 预编码的 `geo` 帧；`GEO_HEADER` 与 `GEO_BROADCAST_SECS` 均开启时存在
+    pub geo_frames: Option<watch::Receiver<Option<Message>>>,
+    /// 运行时日志过滤规则
+    pub log: std::sync::Arc<LogControl>,
+    /// 人数达到该值后对订阅 `delta` 的连接改发增量
+    pub delta_threshold: Option<usize>,
+    pub delta_resync: Duration,
+    /// 后台任务监管（`/readyz`）
+    pub tasks: std::sync::Arc<Supervisor>,
+    /// 虚拟在线成员（`SYNTHETIC_PRESENCE`）
+    pub synthetic: std::sync::Arc<SyntheticLoad>,
+    /// 内存 / CPU 压力下的自动降载（`SHED_*`）
+    pub shed: std::sync::Arc<LoadShedder>,
+    /// 镜像模式下各下游实例的最近上报（节点名 -> 统计），计入在线人数
+    pub mirrors: std::sync::Arc<DashMap<String, MirrorNode>>,
+    /// 本实例节点 ID（`NODE_ID`）
+    pub node_id: String,
+    /// 客户端 IP 允许 / 拒绝名单（`IP_ALLOWLIST` / `IP_DENYLIST`），WS 握手与管理接口共用
+    pub ip_filter: std::sync::Arc<IpFilter>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct WebQuery { pub socket_session_id: Option<String> }
+/// 在线统计快照：`count` 为按 `COUNT_MODE` 口径的展示值
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct OnlineStats {
+    pub count: usize,
+    /// 原始连接数（不含观察者）
+    pub connections: usize,
+    /// 去重后的会话数
+    pub sessions: usize,
+    /// 按用户合并后的访客数
+    pub users: usize,
+}
+
+/// 服务模式：维护模式拒绝新连接；排空模式另外逐步断开现有连接并引导重连到其他实例
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum ServiceMode {
+    #[default]
+    Normal,
+    Maintenance { retry_after_secs: u64 },
+    Drain { retry_after_secs: u64, reconnect_to: Option<String>, drain_secs: u64 },
+}
 
 #[derive(Debug, Deserialize)]
-#[serde(tag = "type", rename_all = "lowercase")]
-enum InMsg { #[serde(rename_all = "camelCase")] UpdateSid { session_id: String } }
+pub struct WebQuery {
+    pub socket_session_id: Option<String>,
+    /// 协议版本，见 `events::negotiate`
+    pub v: Option<u8>,
+    /// 页面来源（浏览器的 WS 握手通常不带 `Referer`，可由客户端传入 `document.referrer`）
+    #[serde(rename = "ref")]
+    pub referrer: Option<String>,
+    pub utm_source: Option<String>,
+    pub utm_medium: Option<String>,
+    pub utm_campaign: Option<String>,
+    pub utm_term: Option<String>,
+    pub utm_content: Option<String>,
+    /// 上次 `hello` 下发的 `resume_token`
+    pub resume: Option<String>,
+    /// `observe=1`：只订阅人数，不计入在线
+    #[serde(default, deserialize_with = "de_flag")]
+    pub observe: bool,
+    /// 订阅的下行事件，见 `events::EventFilter`
+    pub events: Option<String>,
+    /// `write:presence` API 令牌；与 `observe=1` 同时携带时本连接可上报 `mirror`
+    pub token: Option<String>,
+    /// 人数推送节流，见 `events::SyncThrottle`
+    pub min_change: Option<usize>,
+    pub min_interval: Option<String>,
+}
+
+fn de_flag<'de, D: serde::Deserializer<'de>>(d: D) -> Result<bool, D::Error> {
+    let v = Option::<String>::deserialize(d)?;
+    Ok(matches!(v.as_deref().map(str::trim), Some("1" | "true" | "yes")))
+}
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "type", rename_all = "lowercase")]
-enum OutMsg<'a> {
-    Sync { count: usize },
-    Hello { sid: &'a str, count: usize },
+/// 从查询参数与 `Referer` 头提取流量来源；来源仅保留主机名
+fn extract_attribution(headers: &HeaderMap, q: &WebQuery) -> Attribution {
+    let clean = |v: &Option<String>| v.as_deref().map(str::trim).filter(|s| !s.is_empty()).map(|s| s.chars().take(128).collect::<String>());
+    let referrer = clean(&q.referrer)
+        .or_else(|| headers.get("referer").and_then(|v| v.to_str().ok()).map(|s| s.to_string()))
+        .map(|r| parse_host_port(&r.to_ascii_lowercase()).0)
+        .map(|h| h.strip_prefix("www.").map(|s| s.to_string()).unwrap_or(h))
+        .filter(|h| !h.is_empty());
+    Attribution {
+        referrer,
+        utm_source: clean(&q.utm_source),
+        utm_medium: clean(&q.utm_medium),
+        utm_campaign: clean(&q.utm_campaign),
+        utm_term: clean(&q.utm_term),
+        utm_content: clean(&q.utm_content),
+    }
 }
 
 fn extract_session_id(headers: &HeaderMap, query_sid: Option<&str>) -> Option<String> {
@@ -40,37 +178,75 @@ fn extract_session_id(headers: &HeaderMap, query_sid: Option<&str>) -> Option<St
     query_sid.map(|s| s.to_string())
 }
 
+/// 校验客户端提供的会话 ID；配置签名密钥时返回 `<id>` 部分，签名无效返回 `None`
+fn accept_session_id(state: &AppState, raw: &str) -> Option<String> {
+    match &state.session_secret {
+        Some(key) => crate::sign::verify_id(key.as_bytes(), raw).map(str::to_string),
+        None => Some(raw.to_string()),
+    }
+}
+
+/// 客户端 IP；`trust_proxy` 时优先取 `X-Forwarded-For` 首项 / `X-Real-IP`
+pub fn client_ip(headers: &HeaderMap, addr: SocketAddr, trust_proxy: bool) -> String {
+    if trust_proxy {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .or_else(|| headers.get("x-real-ip").and_then(|v| v.to_str().ok()))
+            .map(str::trim)
+            .filter(|v| !v.is_empty());
+        if let Some(ip) = forwarded { return ip.to_string(); }
+    }
+    addr.ip().to_string()
+}
+
 fn origin_allowed(headers: &HeaderMap, whitelist: &HashSet<String>) -> bool {
     if whitelist.iter().any(|s| s.trim() == "*") { return true; }
-    let origin = match headers.get("origin").and_then(|v| v.to_str().ok()) {
-        Some(v) if !v.trim().is_empty() => v.trim().to_ascii_lowercase(),
-        _ => return false,
+    let origin = match normalized_origin(headers) {
+        Some(v) => v,
+        None => return false,
     };
-    let origin_norm = origin.trim_end_matches('/');
+    whitelist.iter().any(|item| origin_matches(&origin, item))
+}
+
+/// 小写、去尾部 `/` 的 `Origin`；缺失或为空时返回 `None`
+fn normalized_origin(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("origin")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_ascii_lowercase().trim_end_matches('/').to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// 单条规则匹配：完整 Origin、域名、域名:端口、后缀通配（`*.example.com` / `.example.com`）、`*`
+fn origin_matches(origin_norm: &str, entry: &str) -> bool {
+    let e = entry.trim().trim_end_matches('/');
+    if e.is_empty() { return false; }
+    if e == "*" { return true; }
+    if e.starts_with("http://") || e.starts_with("https://") {
+        return origin_norm == e;
+    }
     let (host, port) = parse_host_port(origin_norm);
-    for item in whitelist.iter() {
-        let e = item.trim().trim_end_matches('/');
-        if e.is_empty() { continue; }
-        if e.starts_with("http://") || e.starts_with("https://") {
-            if origin_norm == e { return true; }
-            continue;
-        }
-        if let Some(suffix) = e.strip_prefix("*.").or_else(|| e.strip_prefix('.')) {
-            let sfx = suffix.trim_start_matches('.');
-            if host == sfx || host.ends_with(&format!(".{}", sfx)) { return true; }
-            continue;
-        }
-        if let Some((eh, ep)) = e.split_once(':') {
-            if eh == host && Some(ep) == port { return true; }
-            continue;
-        }
-        if e == host { return true; }
+    if let Some(suffix) = e.strip_prefix("*.").or_else(|| e.strip_prefix('.')) {
+        let sfx = suffix.trim_start_matches('.');
+        return host == sfx || host.ends_with(&format!(".{}", sfx));
+    }
+    if let Some((eh, ep)) = e.split_once(':') {
+        return eh == host && Some(ep) == port;
     }
-    false
+    e == host
+}
+
+/// 按首条匹配规则取并发上限，未匹配时取默认值
+fn origin_quota(quotas: &OriginQuotas, origin: Option<&str>) -> Option<usize> {
+    origin
+        .and_then(|o| quotas.rules.iter().find(|(e, _)| origin_matches(o, e)).map(|(_, n)| *n))
+        .or(quotas.default_max)
 }
 
 fn parse_host_port(origin: &str) -> (String, Option<&str>) {
-    let after_scheme = origin.splitn(2, "://").nth(1).unwrap_or(origin);
+    let after_scheme = origin.split_once("://").map(|x| x.1).unwrap_or(origin);
     let authority = after_scheme.split('/').next().unwrap_or(after_scheme);
     let auth = authority.trim_matches(|c| c == '[' || c == ']');
     if let Some(idx) = auth.rfind(':') {
@@ -81,69 +257,701 @@ fn parse_host_port(origin: &str) -> (String, Option<&str>) {
     }
 }
 
+/// 握手阶段采集的连接上下文
+struct ConnCtx {
+    session_id: Option<String>,
+    version: u8,
+    remote_ip: String,
+    subnet: String,
+    origin: Option<String>,
+    attribution: Attribution,
+    observe: bool,
+    user_id: Option<String>,
+    resume: Option<String>,
+    events: EventFilter,
+    throttle: Option<SyncThrottle>,
+    overflow: bool,
+    country: Option<String>,
+    /// 已验证令牌的观察者连接，可上报 `mirror`
+    mirror: bool,
+    /// 入口路径标签，见 `endpoint_label`
+    endpoint: &'static str,
+}
+
 pub async fn ws_web_route(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    path: MatchedPath,
     headers: HeaderMap,
     Query(query): Query<WebQuery>,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
+    let remote_ip = client_ip(&headers, addr, state.trust_proxy);
+    if !state.ip_filter.permits(&remote_ip) {
+        state.metrics.add("activenow_ip_rejected_total", String::new(), 1);
+        return axum::http::StatusCode::FORBIDDEN.into_response();
+    }
     if let Some(whitelist) = &state.origin_whitelist {
         if !whitelist.is_empty() && !origin_allowed(&headers, whitelist) {
             return axum::http::StatusCode::FORBIDDEN.into_response();
         }
     }
-    let sess = extract_session_id(&headers, query.socket_session_id.as_deref());
-    ws.on_upgrade(move |socket| handle_ws_web(socket, state, sess))
+    let origin = normalized_origin(&headers);
+    let over_quota = origin_quota(&state.origin_quotas, origin.as_deref())
+        .is_some_and(|limit| state.registry.origin_live(origin.as_deref()) >= limit);
+    let over_global = state
+        .max_connections
+        .is_some_and(|limit| state.registry.len() - state.registry.overflow_len() >= limit);
+    // 溢出连接只计数不推送，观察者无意义故仍拒绝
+    let overflow = over_quota || over_global;
+    if overflow && (!state.limit_overflow || query.observe) {
+        return axum::http::StatusCode::TOO_MANY_REQUESTS.into_response();
+    }
+    let subnet = state.ip_limits.subnet(&remote_ip);
+    if let Some(limit) = state.ip_limits.max_connections {
+        if state.registry.subnet_live(&subnet) >= limit {
+            return axum::http::StatusCode::TOO_MANY_REQUESTS.into_response();
+        }
+    }
+    // 签名无效的会话 ID 视为未携带，由服务端分配
+    let session_id = extract_session_id(&headers, query.socket_session_id.as_deref())
+        .and_then(|s| accept_session_id(&state, &s));
+    let mut ban_keys = vec![(BanTarget::Ip, remote_ip.as_str()), (BanTarget::Ip, subnet.as_str())];
+    if let Some(s) = &session_id { ban_keys.push((BanTarget::Session, s.as_str())); }
+    if let Some(ban) = state.meta.find_ban(&ban_keys, state.clock.now_ms()).await {
+        return banned_response(&ban);
+    }
+    let mut loop_keys = vec![format!("ip:{subnet}")];
+    if let Some(s) = &session_id { loop_keys.push(format!("session:{s}")); }
+    match state.reconnects.check(&loop_keys, state.clock.now_ms()) {
+        Verdict::Allow => {}
+        Verdict::Penalty { retry_after_secs } => {
+            state.metrics.add("activenow_reconnect_rejected_total", String::new(), 1);
+            let retry = [(axum::http::header::RETRY_AFTER, retry_after_secs.to_string())];
+            return (axum::http::StatusCode::TOO_MANY_REQUESTS, retry).into_response();
+        }
+        Verdict::Ban { key } => {
+            state.metrics.add("activenow_reconnect_bans_total", String::new(), 1);
+            let ban = reconnect_ban(&state, &key).await;
+            return banned_response(&ban);
+        }
+    }
+    let identity = match state.auth.authenticate(&headers, &query).await {
+        Ok(identity) => identity,
+        Err(e) => return e.into_response(),
+    };
+    let mirror = match query.token.as_deref() {
+        Some(token) => match state.auth.authorize(&headers, Some(token), Scope::WritePresence).await {
+            Ok(_) => query.observe,
+            Err(e) => return e.into_response(),
+        },
+        None => false,
+    };
+    match &*state.mode_tx.borrow() {
+        ServiceMode::Normal => {}
+        ServiceMode::Maintenance { retry_after_secs } | ServiceMode::Drain { retry_after_secs, .. } => {
+            let retry = [(axum::http::header::RETRY_AFTER, retry_after_secs.to_string())];
+            return (axum::http::StatusCode::SERVICE_UNAVAILABLE, retry).into_response();
+        }
+    }
+    if state.shed.active() {
+        state.metrics.add("activenow_shed_rejected_total", String::new(), 1);
+        let retry = [(axum::http::header::RETRY_AFTER, SHED_RETRY_SECS.to_string())];
+        return (axum::http::StatusCode::SERVICE_UNAVAILABLE, retry).into_response();
+    }
+    // 协议层硬上限（远大于业务上限），避免超大帧在校验前被整体缓冲
+    let hard_limit = state.max_frame_bytes.saturating_mul(4).max(64 * 1024);
+    let ws = ws.protocols(events::SUBPROTOCOLS).max_message_size(hard_limit).max_frame_size(hard_limit);
+    let subprotocol = ws.selected_protocol().and_then(|p| p.to_str().ok()).map(|s| s.to_string());
+    let ctx = ConnCtx {
+        session_id,
+        version: events::negotiate(query.v, subprotocol.as_deref()),
+        remote_ip,
+        subnet,
+        origin,
+        attribution: extract_attribution(&headers, &query),
+        observe: query.observe,
+        user_id: identity.user_id,
+        resume: query.resume.clone(),
+        events: EventFilter::parse(query.events.as_deref()),
+        throttle: SyncThrottle::parse(query.min_change, query.min_interval.as_deref()),
+        overflow,
+        country: state.geo_header.as_deref().and_then(|h| geo::country(&headers, h)),
+        mirror,
+        endpoint: endpoint_label(path.as_str()),
+    };
+    ws.on_upgrade(move |socket| handle_ws_web(socket, state, ctx))
 }
 
-async fn handle_ws_web(mut ws: WebSocket, state: AppState, session_id: Option<String>) {
-    let sid = new_sid();
-    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
-    let sess_id = session_id.clone().unwrap_or_else(|| sid.clone());
-    state.meta.upsert_identity(&sid, sess_id.clone(), now_ms).await;
-    let count = state.meta.unique_session_count().await;
-    let _ = state.online_tx.send(count);
+async fn handle_ws_web(mut ws: WebSocket, state: AppState, ctx: ConnCtx) {
+    let now_ms = state.clock.now_ms();
+    // 有效且原 sid 不在线时沿用令牌中的身份
+    let resumed = ctx
+        .resume
+        .as_deref()
+        .zip(state.resume.as_ref())
+        .and_then(|(token, keys)| keys.verify(token, now_ms))
+        .filter(|r| !state.registry.contains(&r.sid));
+    let was_resumed = resumed.is_some();
+    let (sid, resumed_session) = match resumed {
+        Some(r) => (r.sid, Some(r.session_id)),
+        None => (state.ids.generate(), None),
+    };
+    // `UPDATE_SID=once` 时已携带会话 ID 的连接不再接受 `updateSid`
+    let mut session_set = ctx.session_id.is_some() || resumed_session.is_some();
+    let sess_id = ctx.session_id.clone().or(resumed_session).unwrap_or_else(|| sid.clone());
+    let mut cur_session = sess_id.clone();
+    let features = state.hello_fields.features.then(|| connection_features(&state, &ctx, session_set));
+    let (control_tx, mut control_rx) = tokio::sync::mpsc::unbounded_channel();
+    state.registry.register(ConnInfo {
+        sid: sid.clone(),
+        session_id: sess_id.clone(),
+        connected_at_ms: now_ms,
+        remote_ip: Some(ctx.remote_ip),
+        subnet: ctx.subnet,
+        origin: ctx.origin.clone(),
+        last_seen_ms: now_ms,
+        msgs_in: 0,
+        msgs_out: 0,
+        malformed: 0,
+        observer: ctx.observe,
+        user_id: ctx.user_id.clone(),
+        rtt_ms: None,
+        overflow: ctx.overflow,
+        country: ctx.country.clone(),
+        control: Some(control_tx),
+    });
+    // 观察者不写入 MetaStore，也不触发人数变化
+    let counted = !ctx.observe;
+    let count = if counted {
+        // 断线重连复用旧 sid，会话 ID 却可能已变：按 sid 取消其待清理任务，否则到期后会清掉本连接的元数据
+        if was_resumed { cancel_pending_leave(&state.pending_leaves, &sid); }
+        // 宽限期内的同 session 重连：直接接替旧连接，人数不抖动
+        if let Some((_, (old_sid, task))) = state.pending_leaves.remove(&sess_id) {
+            task.abort();
+            state.meta.clear(&old_sid, state.clock.now_ms()).await;
+        }
+        state.meta.upsert_identity(&sid, sess_id.clone(), now_ms).await;
+        state.meta.set_attribution(&sid, ctx.attribution).await;
+        if let Some(user) = &ctx.user_id { state.meta.link_user(&sid, user.clone(), now_ms).await; }
+        if let Some(sink) = &state.sink {
+            sink.emit(SinkEvent::Connected { sid: sid.clone(), session_id: sess_id.clone(), origin: ctx.origin.clone(), ts: now_ms });
+        }
+        publish_online(&state).await
+    } else {
+        state.bus.current()
+    };
 
     // 首包：hello（当前在线）
-    let hello = serde_json::to_string(&OutMsg::Hello { sid: &sid, count }).unwrap_or_else(|_| "{}".to_string());
-    if ws.send(Message::Text(hello.into())).await.is_err() { return; }
+    let resume_token = state.resume.as_ref().map(|k| k.issue(&sid, &sess_id, now_ms));
+    let signed_session = state.session_secret.as_ref().map(|k| crate::sign::sign_id(k.as_bytes(), &sess_id));
+    let hello = OutMsg::hello(ctx.version, &sid, &count)
+        .with_resume(resume_token, was_resumed)
+        .with_session(signed_session)
+        .with_overflow(ctx.overflow)
+        .with_server_info(
+            state.hello_fields.version.then_some(env!("CARGO_PKG_VERSION")),
+            state.hello_fields.region.as_deref(),
+            features,
+        );
+    let hello = state.codec.encode(&hello);
+    let m = state.metrics.conn(ctx.endpoint);
+    m.accepted();
+    m.msg_out("hello", frame_len(&hello));
+    if ws.send(hello).await.is_err() {
+        disconnect(&state, &sid, counted, LeaveReason::Error).await;
+        return;
+    }
+    state.registry.on_outbound(&sid);
 
     // 仅订阅在线人数变化
-    let mut rx = state.online_rx.clone();
-    let (mut tx, mut rx_ws) = ws.split();
-    let mut ping_interval = state.ping_interval.map(tokio::time::interval);
+    let mut rx = state.bus.frames();
+    let mut geo_rx = state.geo_frames.clone().filter(|_| ctx.events.geo);
+    // 增量模式：仅当客户端已知人数等于帧的 `base` 时发 `delta`，否则回退为 `sync`；定期以 `sync` 校准
+    let delta_threshold = state.delta_threshold.filter(|_| ctx.events.delta);
+    let mut known_count = count.count;
+    let mut deltas_since_sync = 0u32;
+    let mut resync = delta_threshold.map(|_| tokio::time::interval_at(tokio::time::Instant::now() + state.delta_resync, state.delta_resync));
+    // 节流：上次推送人数的时刻（`hello` 计为一次），以及暂缓变化的补发时刻
+    let mut last_count_at = tokio::time::Instant::now();
+    let mut throttle_at: Option<tokio::time::Instant> = None;
+    let (tx, mut rx_ws) = ws.split();
+    // 下行统一经优先级队列由写任务发送，慢客户端不阻塞本循环
+    let (out, mut writer) = Outbox::spawn(tx, m.clone(), state.registry.clone(), sid.clone());
+    let mut writer_done = false;
+    // 按 `PING_JITTER` 随机错开本连接的 Ping 相位
+    let mut ping_interval = state.ping_interval.map(|every| {
+        let offset = Duration::from_millis(rand::random::<u64>() % (state.ping_jitter.as_millis() as u64 + 1));
+        tokio::time::interval_at(tokio::time::Instant::now() + offset, every)
+    });
+    let mut mode_rx = state.mode_tx.subscribe();
+    // 排空模式下本连接的断开时刻（在排空窗口内随机打散）
+    let mut drain_at: Option<tokio::time::Instant> = None;
+    // 空闲截止：收到任意帧（含 Pong）即顺延
+    let idle = state.idle_timeout;
+    let mut idle_deadline = idle.map(|d| tokio::time::Instant::now() + d);
 
-    loop {
+    let reason = loop {
         tokio::select! {
             msg = rx_ws.next() => {
+                if let Some(Ok(frame)) = &msg {
+                    // 文本帧在解析后按消息类型计数
+                    if let Some(kind) = control_kind(frame) { m.msg_in(kind, frame_len(frame)); }
+                    state.registry.on_inbound(&sid, state.clock.now_ms());
+                    idle_deadline = idle.map(|d| tokio::time::Instant::now() + d);
+                }
                 match msg {
-                    Some(Ok(Message::Text(t))) => {
-                        if let Ok(InMsg::UpdateSid { session_id }) = serde_json::from_str::<InMsg>(&t) {
-                            state.meta.set_session_id(&sid, session_id, now_ms).await;
-                            let count = state.meta.unique_session_count().await;
-                            let _ = state.online_tx.send(count);
+                    Some(Ok(frame @ (Message::Text(_) | Message::Binary(_)))) => {
+                        let binary = matches!(frame, Message::Binary(_));
+                        let data = frame.into_data();
+                        if data.len() > state.max_frame_bytes {
+                            state.registry.update(&sid, |c| c.malformed += 1);
+                            m.msg_in("invalid", data.len());
+                            m.frame_error("too_large");
+                            if ctx.events.error {
+                                let payload = state.codec.encode(&OutMsg::error("too_large", None));
+                                out.push(Priority::Control, "error", payload);
+                            }
+                            out.push(Priority::Control, "close", Message::Close(Some(CloseFrame { code: close_code::SIZE, reason: "message too big".into() })));
+                            break LeaveReason::Error;
+                        }
+                        let parsed = state.codec.decode(&data, binary);
+                        m.msg_in(match &parsed {
+                            Ok(ClientMsg::UpdateSid { .. }) => "updateSid",
+                            Ok(ClientMsg::LinkUser { .. }) => "linkUser",
+                            Ok(ClientMsg::Mirror { .. }) => "mirror",
+                            Err(DecodeError::Unsupported) => "binary",
+                            Err(DecodeError::Invalid(_)) => "invalid",
+                        }, data.len());
+                        match parsed {
+                            Ok(ClientMsg::UpdateSid { session_id }) => {
+                                let allowed = match state.update_sid {
+                                    UpdateSidPolicy::Allow => true,
+                                    UpdateSidPolicy::Once => !session_set,
+                                    UpdateSidPolicy::Deny => false,
+                                };
+                                let Some(session_id) = allowed.then(|| accept_session_id(&state, &session_id)).flatten() else {
+                                    if ctx.events.error {
+                                        let payload = state.codec.encode(&OutMsg::error("session_rejected", None));
+                                        out.push(Priority::Control, "error", payload);
+                                    }
+                                    continue;
+                                };
+                                if state.meta.find_ban(&[(BanTarget::Session, &session_id)], state.clock.now_ms()).await.is_some() {
+                                    soft_close(&out, state.codec.as_ref(), CloseReason::Banned, None, None);
+                                    break LeaveReason::Banned;
+                                }
+                                session_set = true;
+                                if session_id == cur_session { continue; }
+                                let now = state.clock.now_ms();
+                                if !state.privacy_mode { tracing::info!(%sid, from = %cur_session, to = %session_id, "session id changed"); }
+                                if let Some(sink) = &state.sink {
+                                    sink.emit(SinkEvent::SessionChanged { sid: sid.clone(), from: cur_session.clone(), to: session_id.clone(), ts: now });
+                                }
+                                cur_session = session_id.clone();
+                                state.registry.update(&sid, |c| c.session_id = session_id.clone());
+                                if counted {
+                                    state.meta.set_session_id(&sid, session_id, now).await;
+                                    publish_online(&state).await;
+                                }
+                            }
+                            Ok(ClientMsg::LinkUser { user_id }) => {
+                                // 已由 AuthProvider 认证的连接不允许改挂到其他用户
+                                let conflict = ctx.user_id.as_ref().is_some_and(|u| *u != user_id);
+                                if conflict || user_id.is_empty() || !counted {
+                                    if ctx.events.error {
+                                        let payload = state.codec.encode(&OutMsg::error("link_rejected", None));
+                                        out.push(Priority::Control, "error", payload);
+                                    }
+                                } else {
+                                    link_user(&state, &sid, user_id).await;
+                                }
+                            }
+                            Ok(ClientMsg::Mirror { node, count, connections, sessions, users, version }) => {
+                                if ctx.mirror {
+                                    mirror::accept(&state, &sid, node, OnlineStats { count, connections, sessions, users }, version).await;
+                                    publish_online(&state).await;
+                                } else if ctx.events.error {
+                                    let payload = state.codec.encode(&OutMsg::error("mirror_rejected", None));
+                                    out.push(Priority::Control, "error", payload);
+                                }
+                            }
+                            Err(e) => {
+                                state.registry.update(&sid, |c| c.malformed += 1);
+                                let (code, detail) = match e {
+                                    DecodeError::Unsupported => ("binary_unsupported", None),
+                                    DecodeError::Invalid(msg) => ("invalid_message", Some(msg)),
+                                };
+                                m.frame_error(code);
+                                if ctx.events.error {
+                                    let payload = state.codec.encode(&OutMsg::error(code, detail));
+                                    out.push(Priority::Control, "error", payload);
+                                }
+                            }
                         }
                     }
-                    Some(Ok(Message::Close(_))) => break,
-                    Some(Err(_)) => break,
+                    // Ping 载荷为发送时刻（毫秒，大端），据此计算往返时延
+                    Some(Ok(Message::Pong(p))) => {
+                        if let Ok(sent) = <[u8; 8]>::try_from(&p[..]) {
+                            let rtt = state.clock.now_ms().saturating_sub(u64::from_be_bytes(sent));
+                            state.registry.update(&sid, |c| c.rtt_ms = Some(rtt));
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None => break LeaveReason::ClientClose,
+                    Some(Err(_)) => break LeaveReason::Error,
                     _ => {}
                 }
             }
-            changed = rx.changed() => {
+            // 未订阅 `sync` 或溢出连接不等待人数变化，既不编码也不发送
+            changed = rx.changed(), if ctx.events.sync && !ctx.overflow => {
                 if changed.is_ok() {
-                    let payload = serde_json::to_string(&OutMsg::Sync { count: *rx.borrow() }).unwrap_or_else(|_| "{}".to_string());
-                    if tx.send(Message::Text(payload.into())).await.is_err() { break; }
-                } else { break; }
+                    let frame = rx.borrow_and_update().clone();
+                    let decision = ctx.throttle.map_or(ThrottleDecision::Send, |t| t.decide(known_count, frame.stats.count, last_count_at.elapsed()));
+                    throttle_at = match decision {
+                        ThrottleDecision::Wait(Some(left)) => Some(tokio::time::Instant::now() + left),
+                        _ => None,
+                    };
+                    if decision == ThrottleDecision::Send {
+                        // 上一帧仍在队列中时会被覆盖，只能发完整 `sync`
+                        let as_delta = delta_threshold.is_some_and(|t| frame.stats.count >= t && frame.base == known_count) && !out.count_pending();
+                        let (kind, payload) = if as_delta { ("delta", frame.delta()) } else { ("sync", frame.payload(ctx.version)) };
+                        if as_delta { deltas_since_sync += 1 } else { deltas_since_sync = 0 }
+                        known_count = frame.stats.count;
+                        last_count_at = tokio::time::Instant::now();
+                        out.push(Priority::Count, kind, payload);
+                    }
+                } else { break LeaveReason::Shutdown; }
+            }
+            _ = async { if let Some(at) = throttle_at { tokio::time::sleep_until(at).await; } }, if throttle_at.is_some() => {
+                throttle_at = None;
+                let frame = rx.borrow().clone();
+                if frame.stats.count != known_count {
+                    known_count = frame.stats.count;
+                    deltas_since_sync = 0;
+                    last_count_at = tokio::time::Instant::now();
+                    out.push(Priority::Count, "sync", frame.payload(ctx.version));
+                }
+            }
+            _ = async { if let Some(r) = &mut resync { r.tick().await; } }, if resync.is_some() => {
+                if deltas_since_sync > 0 {
+                    let frame = rx.borrow().clone();
+                    let payload = frame.payload(ctx.version);
+                    known_count = frame.stats.count;
+                    deltas_since_sync = 0;
+                    last_count_at = tokio::time::Instant::now();
+                    out.push(Priority::Count, "sync", payload);
+                }
+            }
+            Some(frame) = async {
+                match &mut geo_rx {
+                    Some(r) => r.changed().await.ok().and_then(|_| r.borrow_and_update().clone()),
+                    None => std::future::pending().await,
+                }
+            } => {
+                out.push(Priority::Event, "geo", frame);
             }
             _ = async {
                 if let Some(interval) = &mut ping_interval { interval.tick().await; true } else { tokio::task::yield_now().await; false }
             }, if ping_interval.is_some() => {
-                if tx.send(Message::Ping(Vec::new().into())).await.is_err() { break; }
+                let stamp = state.clock.now_ms().to_be_bytes();
+                if !out.push(Priority::Control, "ping", Message::Ping(stamp.to_vec().into())) { break LeaveReason::Error; }
+            }
+            Ok(()) = mode_rx.changed() => {
+                drain_at = match &*mode_rx.borrow_and_update() {
+                    ServiceMode::Drain { drain_secs, .. } => {
+                        let jitter_ms = rand::random::<u64>() % drain_secs.saturating_mul(1000).saturating_add(1);
+                        Some(tokio::time::Instant::now() + Duration::from_millis(jitter_ms))
+                    }
+                    _ => None,
+                };
+            }
+            _ = async {
+                if let Some(at) = drain_at { tokio::time::sleep_until(at).await }
+            }, if drain_at.is_some() => {
+                let (url, retry_secs) = match &*state.mode_tx.borrow() {
+                    ServiceMode::Drain { reconnect_to, retry_after_secs, .. } => (reconnect_to.clone(), *retry_after_secs),
+                    _ => (None, 0),
+                };
+                // 有迁移目标时立即重连过去，否则按 Retry-After 稍后重连
+                let retry_ms = if url.is_some() { 0 } else { retry_secs.saturating_mul(1000) };
+                soft_close(&out, state.codec.as_ref(), CloseReason::Drain, Some(retry_ms), url.as_deref());
+                break LeaveReason::Drain;
+            }
+            Some(reason) = control_rx.recv() => {
+                let retry_ms = match reason {
+                    CloseReason::Kicked | CloseReason::Banned => None,
+                    _ => Some(SHUTDOWN_RETRY_MS),
+                };
+                soft_close(&out, state.codec.as_ref(), reason, retry_ms, None);
+                break reason.into();
+            }
+            // 写失败（对端已断开）
+            _ = &mut writer, if !writer_done => {
+                writer_done = true;
+                break LeaveReason::Error;
+            }
+            _ = async { if let Some(at) = idle_deadline { tokio::time::sleep_until(at).await } }, if idle_deadline.is_some() => {
+                soft_close(&out, state.codec.as_ref(), CloseReason::Timeout, Some(0), None);
+                break LeaveReason::Timeout;
             }
         }
+    };
+
+    if ctx.mirror && mirror::release(&state, &sid) { publish_online(&state).await; }
+    // 等待已入队的控制帧（`closing` / Close）发出
+    out.close();
+    if !writer_done && tokio::time::timeout(WRITER_FLUSH, &mut writer).await.is_err() { writer.abort(); }
+    disconnect(&state, &sid, counted, reason).await;
+}
+
+/// `hello.features`：本连接实际可用的协议能力
+fn connection_features(state: &AppState, ctx: &ConnCtx, session_set: bool) -> Vec<&'static str> {
+    let update_sid = match state.update_sid {
+        UpdateSidPolicy::Allow => true,
+        UpdateSidPolicy::Once => !session_set,
+        UpdateSidPolicy::Deny => false,
+    };
+    [
+        ("sync", ctx.events.sync && !ctx.overflow),
+        ("error", ctx.events.error),
+        ("geo", ctx.events.geo && state.geo_frames.is_some()),
+        ("delta", ctx.events.delta && state.delta_threshold.is_some() && !ctx.overflow),
+        ("sync_throttle", ctx.throttle.is_some() && ctx.events.sync && !ctx.overflow),
+        ("resume", state.resume.is_some()),
+        ("ping", state.ping_interval.is_some()),
+        ("idle_timeout", state.idle_timeout.is_some()),
+        ("signed_session", state.session_secret.is_some()),
+        ("update_sid", update_sid),
+        ("link_user", !ctx.observe),
+    ]
+    .into_iter()
+    .filter_map(|(name, on)| on.then_some(name))
+    .collect()
+}
+
+/// 指标标签：`/ws` -> `ws`、`/v1/ws/web` -> `v1/ws/web`
+fn endpoint_label(path: &str) -> &'static str {
+    match path {
+        "/v1/ws" => "v1/ws",
+        "/v1/ws/web" => "v1/ws/web",
+        "/web" => "web",
+        _ => "ws",
+    }
+}
+
+/// 高频重连达到上限：按触发的键（网段或会话）临时封禁
+async fn reconnect_ban(state: &AppState, key: &str) -> Ban {
+    let (target, value) = match key.split_once(':') {
+        Some(("session", v)) => (BanTarget::Session, v),
+        _ => (BanTarget::Ip, key.trim_start_matches("ip:")),
+    };
+    let now = state.clock.now_ms();
+    let ban = Ban {
+        target,
+        value: value.to_string(),
+        reason: Some("reconnect_loop".to_string()),
+        created_ms: now,
+        expires_ms: Some(now.saturating_add(state.reconnects.ban_duration().as_millis() as u64)),
+    };
+    tracing::warn!(target = ?ban.target, value = %ban.value, "reconnect loop ban");
+    state.meta.add_ban(ban.clone()).await;
+    ban
+}
+
+/// 握手阶段命中封禁：`403` + `{"error":"banned","reason":...,"expires_ms":...}`
+fn banned_response(ban: &Ban) -> axum::response::Response {
+    let body = serde_json::json!({ "error": "banned", "reason": ban.reason, "expires_ms": ban.expires_ms });
+    (axum::http::StatusCode::FORBIDDEN, axum::Json(body)).into_response()
+}
+
+/// 控制帧的指标类型；数据帧在解码后按消息类型计数
+fn control_kind(frame: &Message) -> Option<&'static str> {
+    Some(match frame {
+        Message::Text(_) | Message::Binary(_) => return None,
+        Message::Ping(_) => "ping",
+        Message::Pong(_) => "pong",
+        Message::Close(_) => "close",
+    })
+}
+
+pub(crate) fn frame_len(frame: &Message) -> usize {
+    match frame {
+        Message::Text(t) => t.len(),
+        Message::Binary(b) | Message::Ping(b) | Message::Pong(b) => b.len(),
+        Message::Close(_) => 0,
     }
+}
+
+/// 降载期间拒绝升级时的 `Retry-After`（秒）
+const SHED_RETRY_SECS: u64 = 10;
+
+/// 进程退出时建议客户端的重连等待
+const SHUTDOWN_RETRY_MS: u64 = 3000;
+
+/// 断开时等待写任务发完控制帧的上限
+const WRITER_FLUSH: Duration = Duration::from_secs(2);
 
-    state.meta.clear(&sid).await;
-    let count = state.meta.unique_session_count().await;
-    let _ = state.online_tx.send(count);
+/// 先发送 `closing` 通知，再以对应关闭码关闭
+fn soft_close(out: &Outbox, codec: &dyn MessageCodec, reason: CloseReason, retry_after_ms: Option<u64>, url: Option<&str>) {
+    out.push(Priority::Control, "closing", codec.encode(&OutMsg::Closing { reason, retry_after_ms, url }));
+    let frame = CloseFrame { code: reason.close_code(), reason: reason.as_str().into() };
+    out.push(Priority::Control, "close", Message::Close(Some(frame)));
+}
+
+async fn disconnect(state: &AppState, sid: &str, counted: bool, reason: LeaveReason) {
+    let info = state.registry.unregister(sid, state.clock.now_ms());
+    if counted {
+        if let (Some(sink), Some(info)) = (&state.sink, &info) {
+            let ts = state.clock.now_ms();
+            let duration_ms = ts.saturating_sub(info.connected_at_ms);
+            sink.emit(SinkEvent::Disconnected { sid: info.sid.clone(), session_id: info.session_id.clone(), reason, duration_ms, ts });
+        }
+        match (state.leave_grace, info) {
+            (Some(grace), Some(info)) => schedule_leave(state, info.sid, info.session_id, grace).await,
+            _ => {
+                state.meta.clear(sid, state.clock.now_ms()).await;
+                publish_online(state).await;
+            }
+        }
+    }
+}
+
+/// 将在线连接关联到用户并发出 `identity_linked` 事件；连接不存在（或为观察者）时返回 `false`
+pub async fn link_user(state: &AppState, sid: &str, user_id: String) -> bool {
+    let now = state.clock.now_ms();
+    if !state.meta.link_user(sid, user_id.clone(), now).await { return false; }
+    state.registry.update(sid, |c| c.user_id = Some(user_id.clone()));
+    if let Some(sink) = &state.sink {
+        let session_id = state.registry.get(sid).map(|c| c.session_id).unwrap_or_default();
+        sink.emit(SinkEvent::IdentityLinked { sid: sid.to_string(), session_id, user_id, ts: now });
+    }
+    publish_online(state).await;
+    true
+}
+
+/// 取消 `sid` 的待清理任务（无论登记在哪个 session 下）；不存在时返回 `false`
+fn cancel_pending_leave(pending: &DashMap<String, (String, AbortHandle)>, sid: &str) -> bool {
+    let mut found = false;
+    pending.retain(|_, (s, task)| {
+        if s != sid { return true; }
+        task.abort();
+        found = true;
+        false
+    });
+    found
+}
+
+/// 延迟 `grace` 后清理连接元数据；同一 session 期间重连或以该 sid 断线重连会取消该任务
+async fn schedule_leave(state: &AppState, sid: String, session_id: String, grace: Duration) {
+    let st = state.clone();
+    let (key, owned) = (session_id.clone(), sid.clone());
+    let task = tokio::spawn(async move {
+        tokio::time::sleep(grace).await;
+        st.pending_leaves.remove_if(&key, |_, (s, _)| *s == owned);
+        st.meta.clear(&owned, st.clock.now_ms()).await;
+        publish_online(&st).await;
+    });
+    // 同一 session 已有待清理连接（多标签页先后关闭）时，旧的立即清理
+    if let Some((old_sid, old)) = state.pending_leaves.insert(session_id, (sid, task.abort_handle())) {
+        old.abort();
+        state.meta.clear(&old_sid, state.clock.now_ms()).await;
+        publish_online(state).await;
+    }
+}
+
+/// 按 MetaStore 与 `COUNT_MODE` 统计在线人数（含镜像下游上报），不广播
+pub async fn online_stats(state: &AppState) -> OnlineStats {
+    let connections = state.meta.connection_count().await;
+    let sessions = state.meta.unique_session_count().await;
+    let users = state.meta.unique_user_count().await;
+    let count = match state.count_mode {
+        CountMode::Session => sessions,
+        CountMode::Connection => connections,
+        CountMode::User => users,
+    };
+    let m = mirror::totals(state);
+    OnlineStats { count: count + m.count, connections: connections + m.connections, sessions: sessions + m.sessions, users: users + m.users }
+}
+
+/// 按 MetaStore 重新统计在线人数并广播，返回最新快照
+pub async fn publish_online(state: &AppState) -> OnlineStats {
+    let stats = online_stats(state).await;
+    state.bus.publish(stats);
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use activenow_protocol::Client;
+
+    use crate::test_support::{self, TestServer};
+
+    /// 断线重连带着新的会话 ID 回来：登记在旧会话下的宽限期清理任务也要取消
+    #[tokio::test]
+    async fn resume_cancels_grace_leave_under_previous_session() {
+        let pending: DashMap<String, (String, AbortHandle)> = DashMap::new();
+        let fired = std::sync::Arc::new(AtomicBool::new(false));
+        let flag = fired.clone();
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+        pending.insert("old-session".to_string(), ("sid-1".to_string(), task.abort_handle()));
+        pending.insert("other".to_string(), ("sid-2".to_string(), tokio::spawn(async {}).abort_handle()));
+
+        // 新连接的会话 ID 不同，按会话接替找不到该任务
+        assert!(pending.remove("new-session").is_none());
+        assert!(cancel_pending_leave(&pending, "sid-1"));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!fired.load(Ordering::SeqCst));
+        assert!(pending.get("old-session").is_none());
+        assert!(pending.get("other").is_some());
+        assert!(!cancel_pending_leave(&pending, "sid-1"));
+    }
+
+    #[tokio::test]
+    async fn join_and_leave_update_online_count() {
+        let server = TestServer::start(test_support::config()).await;
+        let (a, hello) = server.connect("socket_session_id=s1").await;
+        assert_eq!(hello.count, 1);
+        let (b, hello) = server.connect("socket_session_id=s2").await;
+        assert_eq!(hello.count, 2);
+        a.close().await.unwrap();
+        server.wait_online(1).await;
+        b.close().await.unwrap();
+        server.wait_online(0).await;
+    }
+
+    /// 超过每分钟握手上限且违规次数达到 `ban_after` 时封禁网段，之后的握手 `403`
+    #[tokio::test]
+    async fn reconnect_loop_bans_subnet() {
+        let mut cfg = test_support::config();
+        cfg.reconnect.max_per_minute = Some(1);
+        cfg.reconnect.ban_after = Some(1);
+        let server = TestServer::start(cfg).await;
+        let (first, _) = server.connect("socket_session_id=loop").await;
+        first.close().await.unwrap();
+        assert!(Client::connect(&server.ws_url("socket_session_id=loop")).await.is_err());
+        let bans = server.state.meta.list_bans(server.state.clock.now_ms()).await;
+        assert_eq!(bans.len(), 1);
+        assert_eq!(bans[0].target, BanTarget::Ip);
+        assert_eq!(bans[0].reason.as_deref(), Some("reconnect_loop"));
+        assert!(bans[0].expires_ms.is_some_and(|t| t > server.state.clock.now_ms()));
+        assert!(Client::connect(&server.ws_url("socket_session_id=other")).await.is_err());
+    }
+
+    /// IPv4 映射写法的封禁同样命中 IPv4 客户端
+    #[tokio::test]
+    async fn ip_ban_matches_mapped_address() {
+        let server = TestServer::start(test_support::config()).await;
+        let (status, _) = server.http("POST", "/v1/admin/bans", Some(r#"{"target":"ip","value":"::ffff:127.0.0.1"}"#)).await;
+        assert_eq!(status, 201);
+        assert!(Client::connect(&server.ws_url("socket_session_id=s1")).await.is_err());
+        let (status, _) = server.http("DELETE", "/v1/admin/bans?target=ip&value=127.0.0.1", None).await;
+        assert_eq!(status, 204);
+        server.connect("socket_session_id=s1").await;
+    }
 }
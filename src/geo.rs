@@ -0,0 +1,50 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::ws::Message;
+use axum::http::HeaderMap;
+use tokio::sync::watch;
+
+use crate::codec::SharedCodec;
+use crate::events::OutMsg;
+use crate::registry::ConnRegistry;
+use crate::supervisor::Supervisor;
+use crate::time::SharedClock;
+
+/// 从代理注入的国家头（如 Cloudflare `CF-IPCountry`）读取两位国家代码；`XX`、`T1` 等非国家值视为未知
+pub fn country(headers: &HeaderMap, header: &str) -> Option<String> {
+    let v = headers.get(header)?.to_str().ok()?.trim();
+    let code = v.to_ascii_uppercase();
+    let valid = code.len() == 2 && code.bytes().all(|b| b.is_ascii_uppercase()) && code != "XX";
+    valid.then_some(code)
+}
+
+/// 定期汇总国家分布并编码为 `geo` 帧；分布不变时不重复推送
+pub fn spawn_broadcast(
+    tasks: &Arc<Supervisor>,
+    registry: Arc<ConnRegistry>,
+    every: Duration,
+    clock: SharedClock,
+    codec: SharedCodec,
+    shedding: watch::Receiver<bool>,
+) -> watch::Receiver<Option<Message>> {
+    let (tx, rx) = watch::channel(None);
+    tasks.spawn("geo_broadcast", move || {
+        let (tx, registry, clock, codec, shedding) = (tx.clone(), registry.clone(), clock.clone(), codec.clone(), shedding.clone());
+        async move {
+            let mut tick = tokio::time::interval(every);
+            let mut last = None;
+            loop {
+                tick.tick().await;
+                // 降载期间暂停推送
+                if *shedding.borrow() { continue; }
+                let geo = registry.country_breakdown(clock.now_ms());
+                let key = (geo.countries.clone(), geo.unknown);
+                if last.as_ref() == Some(&key) { continue; }
+                last = Some(key);
+                tx.send_replace(Some(codec.encode(&OutMsg::Geo(&geo))));
+            }
+        }
+    });
+    rx
+}
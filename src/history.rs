@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::config::{CronSchedule, StatsZone};
+use crate::gateway::OnlineStats;
+use crate::supervisor::Supervisor;
+use crate::time::SharedClock;
+
+const HOUR_MS: u64 = 3_600_000;
+const DAY_MS: u64 = 86_400_000;
+
+/// 在线人数采样环形缓冲（仅内存），附整点 / 整日汇总
+pub struct OnlineHistory {
+    interval: Duration,
+    capacity: usize,
+    retention_ms: u64,
+    samples: Mutex<VecDeque<(u64, usize)>>,
+    rollups: Mutex<Rollups>,
+    /// 日汇总的换日时区，同 `STATS_TIMEZONE`
+    zone: StatsZone,
+    rollup_retention_ms: u64,
+}
+
+#[derive(Default)]
+struct Rollups {
+    hourly: BTreeMap<u64, Rollup>,
+    daily: BTreeMap<u64, Rollup>,
+    /// 早于此时刻的样本已计入汇总
+    watermark: u64,
+}
+
+/// 一个时间桶内的在线人数汇总
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rollup {
+    pub start_ms: u64,
+    pub min: usize,
+    pub max: usize,
+    pub avg: f64,
+    pub samples: u64,
+    #[serde(skip)]
+    sum: u64,
+}
+
+impl Rollup {
+    fn new(start_ms: u64) -> Self {
+        Self { start_ms, min: usize::MAX, max: 0, avg: 0.0, samples: 0, sum: 0 }
+    }
+
+    fn add(&mut self, v: usize) {
+        self.min = self.min.min(v);
+        self.max = self.max.max(v);
+        self.samples += 1;
+        self.sum += v as u64;
+        self.avg = self.sum as f64 / self.samples as f64;
+    }
+}
+
+/// 样本与汇总的完整转储，见 `/v1/admin/export`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HistoryDump {
+    /// `(时刻毫秒, 在线人数)`，按时间升序
+    pub samples: Vec<(u64, usize)>,
+    pub hourly: Vec<Rollup>,
+    pub daily: Vec<Rollup>,
+    /// 早于此时刻的样本已计入汇总
+    pub watermark: u64,
+}
+
+/// 导入转储新增的条目数
+#[derive(Debug, Default, Serialize)]
+pub struct HistoryImport {
+    pub samples: usize,
+    pub hourly: usize,
+    pub daily: usize,
+}
+
+/// 一次汇总的结果
+#[derive(Debug, Default, Serialize)]
+pub struct RollupReport {
+    /// 计入汇总的样本数
+    pub rolled: usize,
+    /// 超出 `HISTORY_RETENTION` 被清理的原始样本数
+    pub pruned_samples: usize,
+    /// 超出 `ROLLUP_RETENTION_DAYS` 被清理的汇总桶数
+    pub pruned_rollups: usize,
+}
+
+/// 汇总粒度
+#[derive(Debug, Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    #[default]
+    Hour,
+    Day,
+}
+
+/// 降采样结果：`values[i]` 为 `[start_ms + i*step_ms, +step_ms)` 内的最大值，无样本为 `null`
+#[derive(Debug, Serialize)]
+pub struct Sparkline {
+    pub start_ms: u64,
+    pub step_ms: u64,
+    pub values: Vec<Option<usize>>,
+}
+
+impl OnlineHistory {
+    pub fn new(interval: Duration, retention: Duration) -> Self {
+        let capacity = (retention.as_millis() / interval.as_millis().max(1)).max(1) as usize;
+        Self {
+            interval,
+            capacity,
+            retention_ms: retention.as_millis() as u64,
+            samples: Mutex::new(VecDeque::with_capacity(capacity)),
+            rollups: Mutex::new(Rollups::default()),
+            zone: StatsZone::default(),
+            rollup_retention_ms: 90 * DAY_MS,
+        }
+    }
+
+    /// 日汇总按 `zone` 的本地零点换日，汇总保留 `retention`
+    pub fn with_rollup(mut self, zone: StatsZone, retention: Duration) -> Self {
+        self.zone = zone;
+        self.rollup_retention_ms = retention.as_millis() as u64;
+        self
+    }
+
+    pub fn record(&self, ts_ms: u64, online: usize) {
+        let mut q = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        if q.len() == self.capacity { q.pop_front(); }
+        q.push_back((ts_ms, online));
+    }
+
+    pub fn sparkline(&self, window: Duration, points: usize, now_ms: u64) -> Sparkline {
+        let points = points.clamp(1, 1000);
+        let window_ms = (window.as_millis() as u64).max(points as u64);
+        let step_ms = window_ms.div_ceil(points as u64);
+        let start_ms = now_ms.saturating_sub(step_ms * points as u64);
+        let mut values = vec![None; points];
+        let q = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        for (ts, v) in q.iter().rev().take_while(|(ts, _)| *ts >= start_ms) {
+            let i = (((ts - start_ms) / step_ms) as usize).min(points - 1);
+            values[i] = Some(values[i].map_or(*v, |m: usize| m.max(*v)));
+        }
+        Sparkline { start_ms, step_ms, values }
+    }
+
+    /// 将已结束整点内尚未汇总的样本计入小时 / 日汇总，并清理过期样本与汇总；可重复调用
+    pub fn rollup(&self, now_ms: u64) -> RollupReport {
+        let mut report = RollupReport::default();
+        let hour_end = now_ms / HOUR_MS * HOUR_MS;
+        let mut q = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let mut guard = self.rollups.lock().unwrap_or_else(|e| e.into_inner());
+        let r = &mut *guard;
+        let watermark = r.watermark;
+        for &(ts, v) in q.iter().filter(|(ts, _)| *ts >= watermark && *ts < hour_end) {
+            let hour = ts / HOUR_MS * HOUR_MS;
+            let day = self.zone.day_start(ts);
+            r.hourly.entry(hour).or_insert_with(|| Rollup::new(hour)).add(v);
+            r.daily.entry(day).or_insert_with(|| Rollup::new(day)).add(v);
+            report.rolled += 1;
+        }
+        r.watermark = r.watermark.max(hour_end);
+
+        let cutoff = now_ms.saturating_sub(self.retention_ms);
+        while q.front().is_some_and(|(ts, _)| *ts < cutoff) {
+            q.pop_front();
+            report.pruned_samples += 1;
+        }
+        let cutoff = now_ms.saturating_sub(self.rollup_retention_ms);
+        for buckets in [&mut r.hourly, &mut r.daily] {
+            let before = buckets.len();
+            buckets.retain(|start, _| *start >= cutoff);
+            report.pruned_rollups += before - buckets.len();
+        }
+        report
+    }
+
+    /// 最近 `limit` 个汇总桶，按时间升序
+    pub fn rollups(&self, granularity: Granularity, limit: usize) -> Vec<Rollup> {
+        let r = self.rollups.lock().unwrap_or_else(|e| e.into_inner());
+        let buckets = match granularity {
+            Granularity::Hour => &r.hourly,
+            Granularity::Day => &r.daily,
+        };
+        let mut v: Vec<_> = buckets.values().rev().take(limit).cloned().collect();
+        v.reverse();
+        v
+    }
+
+    pub fn dump(&self) -> HistoryDump {
+        let q = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let r = self.rollups.lock().unwrap_or_else(|e| e.into_inner());
+        HistoryDump {
+            samples: q.iter().copied().collect(),
+            hourly: r.hourly.values().cloned().collect(),
+            daily: r.daily.values().cloned().collect(),
+            watermark: r.watermark,
+        }
+    }
+
+    /// 合并转储：样本按时刻去重（本地优先），汇总桶同一起点保留样本数较多的一方；重复导入结果不变
+    pub fn restore(&self, dump: HistoryDump) -> HistoryImport {
+        let mut report = HistoryImport::default();
+        let mut q = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let mut guard = self.rollups.lock().unwrap_or_else(|e| e.into_inner());
+        let r = &mut *guard;
+
+        let mut merged: BTreeMap<u64, usize> = dump.samples.into_iter().collect();
+        merged.extend(q.iter().copied());
+        report.samples = merged.len().saturating_sub(q.len());
+        let skip = merged.len().saturating_sub(self.capacity);
+        *q = merged.into_iter().skip(skip).collect();
+
+        for (buckets, incoming, added) in [(&mut r.hourly, dump.hourly, &mut report.hourly), (&mut r.daily, dump.daily, &mut report.daily)] {
+            for mut b in incoming.into_iter().filter(|b| b.samples > 0) {
+                b.sum = (b.avg * b.samples as f64).round() as u64;
+                match buckets.get(&b.start_ms) {
+                    Some(cur) if cur.samples >= b.samples => {}
+                    cur => {
+                        if cur.is_none() { *added += 1; }
+                        buckets.insert(b.start_ms, b);
+                    }
+                }
+            }
+        }
+        // 已计入导入汇总的样本不再重复汇总
+        r.watermark = r.watermark.max(dump.watermark);
+        report
+    }
+
+    /// 按 `ROLLUP_SCHEDULE` 定时汇总
+    pub fn spawn_rollup(self: std::sync::Arc<Self>, tasks: &std::sync::Arc<Supervisor>, schedule: CronSchedule, clock: SharedClock) {
+        tasks.spawn("stats_rollup", move || {
+            let (this, schedule, clock) = (self.clone(), schedule.clone(), clock.clone());
+            async move {
+                loop {
+                    let now = clock.now_ms();
+                    let next = schedule.next_after(now, &this.zone);
+                    tokio::time::sleep(Duration::from_millis(next.saturating_sub(now))).await;
+                    let report = this.rollup(clock.now_ms());
+                    tracing::info!(rolled = report.rolled, pruned_samples = report.pruned_samples, pruned_rollups = report.pruned_rollups, "stats rollup");
+                }
+            }
+        });
+    }
+
+    /// 按固定间隔采样当前在线人数
+    pub fn spawn_sampler(self: std::sync::Arc<Self>, tasks: &std::sync::Arc<Supervisor>, rx: watch::Receiver<OnlineStats>, clock: SharedClock) {
+        tasks.spawn("history_sampler", move || {
+            let (this, rx, clock) = (self.clone(), rx.clone(), clock.clone());
+            async move {
+                let mut tick = tokio::time::interval(this.interval);
+                loop {
+                    tick.tick().await;
+                    this.record(clock.now_ms(), rx.borrow().count);
+                }
+            }
+        });
+    }
+}
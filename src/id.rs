@@ -1,5 +1,98 @@
-pub fn new_sid() -> String {
-    // 21 chars nanoid; short, URL-safe
-    nanoid::nanoid!()
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+
+use crate::config::SidGeneratorKind;
+
+/// 连接 `sid` 生成器；嵌入方可自行实现并注入 `AppState`
+pub trait IdGenerator: Send + Sync {
+    fn generate(&self) -> String;
+}
+
+/// 按配置构造生成器；默认 21 位 nanoid（短、URL 安全）
+pub fn from_config(kind: &SidGeneratorKind) -> Arc<dyn IdGenerator> {
+    match kind {
+        SidGeneratorKind::Nanoid { alphabet, len } => Arc::new(NanoidGenerator::new(alphabet.as_deref(), *len)),
+        SidGeneratorKind::UuidV7 => Arc::new(UuidV7Generator),
+        SidGeneratorKind::Snowflake { node_id } => Arc::new(SnowflakeGenerator::new(*node_id)),
+    }
+}
+
+/// nanoid，可自定义字母表与长度
+pub struct NanoidGenerator {
+    alphabet: Vec<char>,
+    len: usize,
+}
+
+impl NanoidGenerator {
+    pub fn new(alphabet: Option<&str>, len: usize) -> Self {
+        let alphabet = alphabet
+            .map(|s| s.chars().collect::<Vec<_>>())
+            .filter(|v| v.len() > 1)
+            .unwrap_or_else(|| nanoid::alphabet::SAFE.to_vec());
+        Self { alphabet, len: len.max(1) }
+    }
+}
+
+impl IdGenerator for NanoidGenerator {
+    fn generate(&self) -> String { nanoid::format(nanoid::rngs::default, &self.alphabet, self.len) }
 }
 
+/// UUIDv7（按时间可排序）
+pub struct UuidV7Generator;
+
+impl IdGenerator for UuidV7Generator {
+    fn generate(&self) -> String {
+        let ms = unix_ms() & 0xFFFF_FFFF_FFFF;
+        let mut rng = rand::thread_rng();
+        let rand_a: u16 = rng.gen::<u16>() & 0x0FFF;
+        let rand_b: u64 = rng.gen::<u64>() & 0x3FFF_FFFF_FFFF_FFFF;
+        let hi = (ms << 16) | 0x7000 | rand_a as u64;
+        let lo = 0x8000_0000_0000_0000 | rand_b;
+        format!(
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            hi >> 32,
+            (hi >> 16) & 0xFFFF,
+            hi & 0xFFFF,
+            lo >> 48,
+            lo & 0xFFFF_FFFF_FFFF
+        )
+    }
+}
+
+/// Snowflake：41 位毫秒时间戳（自 2024-01-01 起）+ 10 位节点 + 12 位序列
+pub struct SnowflakeGenerator {
+    node_id: u64,
+    /// (上次毫秒, 序列号)
+    last: Mutex<(u64, u64)>,
+}
+
+const SNOWFLAKE_EPOCH_MS: u64 = 1_704_067_200_000;
+
+impl SnowflakeGenerator {
+    pub fn new(node_id: u16) -> Self {
+        Self { node_id: (node_id as u64) & 0x3FF, last: Mutex::new((0, 0)) }
+    }
+}
+
+impl IdGenerator for SnowflakeGenerator {
+    fn generate(&self) -> String {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        let mut now = unix_ms().saturating_sub(SNOWFLAKE_EPOCH_MS);
+        if now <= last.0 {
+            // 同一毫秒（或时钟回拨）：沿用上次时间戳递增序列，溢出则借用下一毫秒
+            now = last.0;
+            last.1 = (last.1 + 1) & 0xFFF;
+            if last.1 == 0 { now += 1; }
+        } else {
+            last.1 = 0;
+        }
+        last.0 = now;
+        ((now << 22) | (self.node_id << 12) | last.1).to_string()
+    }
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
@@ -0,0 +1,104 @@
+use std::net::IpAddr;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+
+/// 网段，如 `10.0.0.0/8`、`2001:db8::/32`；单个地址视为 `/32` / `/128`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    net: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        let (addr, prefix) = match raw.split_once('/') {
+            Some((a, p)) => (a, Some(p.parse::<u8>().ok()?)),
+            None => (raw, None),
+        };
+        let ip: IpAddr = addr.parse().ok()?;
+        let max = if ip.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        if prefix > max { return None; }
+        // `::ffff:a.b.c.d/N`（N >= 96）按对应的 IPv4 网段处理
+        let (net, prefix) = match canonical(ip) {
+            v4 @ IpAddr::V4(_) if ip.is_ipv6() => if prefix >= 96 { (v4, prefix - 96) } else { (ip, prefix) },
+            net => (net, prefix),
+        };
+        Some(Self { net: mask(net, prefix), prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = canonical(ip);
+        ip.is_ipv4() == self.net.is_ipv4() && mask(ip, self.prefix) == self.net
+    }
+}
+
+impl std::fmt::Display for Cidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "{}/{}", self.net, self.prefix) }
+}
+
+/// IPv4 映射的 IPv6 地址按 IPv4 处理
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    }
+}
+
+/// 地址或网段的规范写法（IPv4 映射地址按 IPv4，网段按前缀对齐），用于封禁的存取与比较；无法解析的原样返回
+pub fn normalize(raw: &str) -> String {
+    let raw = raw.trim();
+    if raw.contains('/') { return Cidr::parse(raw).map_or_else(|| raw.to_string(), |c| c.to_string()); }
+    raw.parse::<IpAddr>().map_or_else(|_| raw.to_string(), |ip| canonical(ip).to_string())
+}
+
+fn mask(ip: IpAddr, prefix: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => IpAddr::V4((u32::from(v4) & u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)).into()),
+        IpAddr::V6(v6) => IpAddr::V6((u128::from(v6) & u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0)).into()),
+    }
+}
+
+/// 解析网段列表；失败时返回首个无法解析的条目
+pub fn parse_all(items: &[String]) -> Result<Vec<Cidr>, String> {
+    items.iter().map(|s| Cidr::parse(s).ok_or_else(|| s.clone())).collect()
+}
+
+/// 允许 / 拒绝名单的文本形式（管理接口读写）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IpLists {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+/// 客户端 IP 访问控制（`IP_ALLOWLIST` / `IP_DENYLIST`）：先查拒绝名单，允许名单非空时只放行其中网段
+#[derive(Debug, Default)]
+pub struct IpFilter {
+    lists: RwLock<(Vec<Cidr>, Vec<Cidr>)>,
+}
+
+impl IpFilter {
+    pub fn new(allow: Vec<Cidr>, deny: Vec<Cidr>) -> Self { Self { lists: RwLock::new((allow, deny)) } }
+
+    /// 无法解析的地址仅在允许名单为空时放行
+    pub fn permits(&self, ip: &str) -> bool {
+        let (allow, deny) = &*self.lists.read().unwrap();
+        if allow.is_empty() && deny.is_empty() { return true; }
+        match ip.parse::<IpAddr>() {
+            Ok(ip) => !deny.iter().any(|c| c.contains(ip)) && (allow.is_empty() || allow.iter().any(|c| c.contains(ip))),
+            Err(_) => allow.is_empty(),
+        }
+    }
+
+    pub fn lists(&self) -> IpLists {
+        let (allow, deny) = &*self.lists.read().unwrap();
+        let text = |v: &[Cidr]| v.iter().map(Cidr::to_string).collect();
+        IpLists { allow: text(allow), deny: text(deny) }
+    }
+
+    pub fn set(&self, allow: Vec<Cidr>, deny: Vec<Cidr>) {
+        *self.lists.write().unwrap() = (allow, deny);
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tracing_subscriber::{fmt, reload, EnvFilter};
+
+/// 运行时可调整的日志过滤规则（`EnvFilter` 语法，如 `info,activenow::gateway=debug`）
+pub struct LogControl {
+    handle: reload::Handle<EnvFilter, fmt::Formatter>,
+    current: Mutex<String>,
+    /// 自动恢复任务；再次调整时取消
+    revert: Mutex<Option<tokio::task::AbortHandle>>,
+}
+
+impl LogControl {
+    /// 按 `RUST_LOG`（默认 `info`）初始化全局订阅者
+    pub fn init() -> Arc<Self> {
+        let spec = std::env::var("RUST_LOG").ok().filter(|s| EnvFilter::try_new(s).is_ok()).unwrap_or_else(|| "info".to_string());
+        let builder = fmt().with_env_filter(EnvFilter::new(&spec)).with_filter_reloading();
+        let handle = builder.reload_handle();
+        builder.init();
+        Arc::new(Self { handle, current: Mutex::new(spec), revert: Mutex::new(None) })
+    }
+
+    /// 不安装全局订阅者，供进程内测试构造 `AppState`
+    #[cfg(test)]
+    pub fn detached() -> Arc<Self> {
+        let (_, handle) = reload::Layer::<EnvFilter, fmt::Formatter>::new(EnvFilter::new("off"));
+        Arc::new(Self { handle, current: Mutex::new("off".to_string()), revert: Mutex::new(None) })
+    }
+
+    pub fn current(&self) -> String { self.current.lock().unwrap().clone() }
+
+    /// 替换过滤规则；`revert_after` 到期后恢复为调整前的规则
+    pub fn set(self: &Arc<Self>, spec: &str, revert_after: Option<Duration>) -> Result<(), String> {
+        let previous = self.current();
+        self.apply(spec)?;
+        let task = revert_after.map(|d| {
+            let this = self.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(d).await;
+                if this.apply(&previous).is_ok() { tracing::info!(filter = %previous, "log filter reverted"); }
+            })
+            .abort_handle()
+        });
+        if let Some(old) = std::mem::replace(&mut *self.revert.lock().unwrap(), task) { old.abort(); }
+        Ok(())
+    }
+
+    fn apply(&self, spec: &str) -> Result<(), String> {
+        let filter = EnvFilter::try_new(spec).map_err(|e| e.to_string())?;
+        self.handle.reload(filter).map_err(|e| e.to_string())?;
+        *self.current.lock().unwrap() = spec.to_string();
+        Ok(())
+    }
+}
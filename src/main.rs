@@ -1,70 +1,488 @@
 mod id;
+mod ipfilter;
 mod gateway;
 
 use std::net::SocketAddr;
 
-use axum::{routing::get, Router, extract::State, Json};
-use tracing_subscriber::{fmt, EnvFilter};
+use axum::{routing::get, Router, extract::{Query, State}, http::StatusCode, Json};
 use gateway::ws_web_route;
+mod admin;
+mod alerts;
+mod assets;
+mod auth;
+mod bench;
+mod bundle;
+mod bus;
+mod codec;
+mod cluster;
 mod config;
+mod events;
+mod geo;
+mod history;
+mod logging;
 mod meta;
+mod metrics;
+mod mirror;
+mod outbox;
+mod preflight;
+mod reconnect;
+mod shed;
+mod registry;
+mod resume;
+mod sign;
+mod sink;
+mod supervisor;
+mod synthetic;
+mod tokens;
+mod time;
+#[cfg(test)]
+mod test_support;
+mod webhook;
 
 #[tokio::main]
 async fn main() {
-    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-    fmt().with_env_filter(env_filter).init();
+    // `activenow healthcheck [url]`：供容器 HEALTHCHECK 使用，无需镜像内安装 curl
+    if std::env::args().nth(1).as_deref() == Some("healthcheck") {
+        std::process::exit(healthcheck(std::env::args().nth(2)).await);
+    }
+    // `activenow bench [options]`：压测目标实例，见 `bench::BenchOpts`
+    if std::env::args().nth(1).as_deref() == Some("bench") {
+        match bench::BenchOpts::parse(std::env::args().skip(2), config::Config::from_env().port) {
+            Ok(opts) => std::process::exit(bench::run(opts).await),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let log = logging::LogControl::init();
 
     let cfg = config::Config::from_env();
+    // 打印生效配置；存在硬错误时拒绝启动。`activenow check-config` 只做检查
+    let config_ok = preflight::report(&cfg);
+    if std::env::args().nth(1).as_deref() == Some("check-config") {
+        std::process::exit(if config_ok { 0 } else { 2 });
+    }
+    if !config_ok { std::process::exit(2); }
+    let state = build_state(&cfg, log);
+    // 独立管理端口时公共端口不暴露任何管理接口，令牌泄露也无法从公网调用
+    let admin_enabled = state.admin_token.is_some() || !state.tokens.is_empty();
+    match (admin_enabled, cfg.admin_addr) {
+        (true, Some(addr)) => {
+            let admin_app = admin::routes(state.clone(), cfg.synthetic_presence).with_state(state.clone());
+            let listener = tokio::net::TcpListener::bind(addr).await.expect("bind admin port");
+            tracing::info!(%addr, "admin listening");
+            tokio::spawn(async move {
+                if let Err(e) = axum::serve(listener, admin_app.into_make_service_with_connect_info::<SocketAddr>()).await {
+                    tracing::error!(error = %e, "admin server error");
+                }
+            });
+        }
+        (false, Some(_)) => tracing::warn!("ADMIN_PORT set without ADMIN_TOKEN or API_TOKENS, admin endpoints disabled"),
+        _ => {}
+    }
+    let app = app_router(&cfg, &state, admin_enabled && cfg.admin_addr.is_none());
+    let shutdown = shutdown_signal(state.clone());
+    let app = app.with_state(state);
 
-    let (online_tx, online_rx) = tokio::sync::watch::channel::<usize>(0);
-    let meta_backend: std::sync::Arc<dyn meta::MetaStore> = std::sync::Arc::new(meta::MemoryMetaStore::new());
+    let addr: SocketAddr = ([0,0,0,0], cfg.port).into();
+    tracing::info!(%addr, "listening");
+    let listener = tokio::net::TcpListener::bind(addr).await.expect("bind port");
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown)
+        .await
+        .expect("server error");
+}
 
-    let state = gateway::AppState {
+/// 按配置构造共享状态并经 Supervisor 启动后台任务
+fn build_state(cfg: &config::Config, log: std::sync::Arc<logging::LogControl>) -> gateway::AppState {
+    let clock: time::SharedClock = std::sync::Arc::new(time::SystemClock);
+
+    let codec: codec::SharedCodec = std::sync::Arc::new(codec::JsonCodec);
+    let metrics: std::sync::Arc<metrics::Metrics> = Default::default();
+    // 后台任务统一经 Supervisor 启动：panic 后退避重启，状态见 `/readyz`
+    let tasks = supervisor::Supervisor::new(clock.clone());
+    let shed = shed::LoadShedder::new(cfg.shed.clone());
+    let bus: std::sync::Arc<dyn bus::EventBus> = std::sync::Arc::new(bus::LocalBus::new(cfg.sync_batch, clock.clone(), codec.clone(), metrics.clone(), &shed, &tasks));
+    let mut memory_store = meta::MemoryMetaStore::new().with_timezone(cfg.stats_timezone);
+    if let Some(path) = &cfg.bans_file {
+        memory_store = memory_store.with_bans_file(path.into());
+    }
+    if let Some(path) = &cfg.daily_stats_file {
+        memory_store = memory_store.with_daily_file(path.into(), clock.now_ms());
+    }
+    let memory_store = std::sync::Arc::new(memory_store);
+    memory_store.clone().spawn_daily_persist(&tasks, cfg.daily_stats_flush, clock.clone());
+    let meta_backend: std::sync::Arc<dyn meta::MetaStore> = memory_store;
+    let api_tokens = std::sync::Arc::new(tokens::ApiTokens::new(cfg.api_tokens.clone()));
+
+    let mut state = gateway::AppState {
         ping_interval: cfg.ping_interval,
+        ping_jitter: cfg.ping_jitter,
         meta: meta_backend,
-        online_tx,
-        online_rx,
+        bus,
         origin_whitelist: cfg.allowed_origins.clone(),
+        count_mode: cfg.count_mode,
+        ids: id::from_config(&cfg.sid_generator),
+        registry: std::sync::Arc::new(registry::ConnRegistry::new(cfg.churn_window)),
+        admin_token: cfg.admin_token.clone(),
+        tokens: api_tokens.clone(),
+        trust_proxy: cfg.trust_proxy,
+        privacy_mode: cfg.privacy_mode,
+        mode_tx: tokio::sync::watch::channel(gateway::ServiceMode::default()).0,
+        max_frame_bytes: cfg.max_frame_bytes,
+        idle_timeout: cfg.idle_timeout,
+        auth: std::sync::Arc::new(auth::TokenAuth { admin_token: cfg.admin_token.clone(), tokens: api_tokens }),
+        origin_quotas: cfg.origin_quotas.clone(),
+        resume: (!cfg.resume_ttl.is_zero())
+            .then(|| std::sync::Arc::new(resume::ResumeKeys::new(cfg.resume_secret.as_deref(), cfg.resume_ttl))),
+        history: std::sync::Arc::new(
+            history::OnlineHistory::new(cfg.history_interval, cfg.history_retention)
+                .with_rollup(cfg.stats_timezone, cfg.rollup_retention),
+        ),
+        sink: sink::EventPipeline::spawn(
+            &tasks,
+            cfg.event_sink.as_deref().and_then(|spec| {
+                let sink = sink::from_config(spec, cfg.event_signing_secret.as_deref());
+                if sink.is_none() { tracing::warn!(spec, "unsupported EVENT_SINK, ignored"); }
+                sink
+            }),
+            cfg.event_sink_buffer,
+            cfg.event_history,
+            cfg.privacy_mode,
+        ),
+        leave_grace: cfg.leave_grace,
+        pending_leaves: Default::default(),
+        ip_limits: cfg.ip_limits.clone(),
+        clock: clock.clone(),
+        metrics,
+        session_secret: cfg.session_secret.clone(),
+        update_sid: cfg.update_sid,
+        max_connections: cfg.max_connections,
+        limit_overflow: cfg.limit_overflow,
+        codec,
+        hello_fields: cfg.hello_fields.clone(),
+        reconnects: std::sync::Arc::new(reconnect::ReconnectGuard::new(cfg.reconnect.clone())),
+        geo_header: cfg.geo_header.clone(),
+        geo_frames: None,
+        log,
+        delta_threshold: cfg.delta_threshold,
+        delta_resync: cfg.delta_resync,
+        tasks: tasks.clone(),
+        synthetic: Default::default(),
+        shed: shed.clone(),
+        mirrors: Default::default(),
+        node_id: cfg.node_id.clone(),
+        ip_filter: std::sync::Arc::new(ipfilter::IpFilter::new(cfg.ip_allowlist.clone(), cfg.ip_denylist.clone())),
     };
 
-    // 打印运行时环境配置，便于排障
-    log_runtime_env(&cfg);
+    if let (Some(_), Some(every)) = (&cfg.geo_header, cfg.geo_interval) {
+        state.geo_frames = Some(geo::spawn_broadcast(&tasks, state.registry.clone(), every, clock.clone(), state.codec.clone(), shed.subscribe()));
+    }
 
     // 仅在线人数，移除房间清理与日统计
+    shed.spawn_monitor(&tasks);
+    state.history.clone().spawn_sampler(&tasks, state.bus.subscribe(), clock.clone());
+    state.reconnects.clone().spawn_sweeper(&tasks, clock.clone());
+    if let Some(schedule) = cfg.rollup_schedule.clone() {
+        state.history.clone().spawn_rollup(&tasks, schedule, clock.clone());
+    }
+    if cfg.alerts.enabled() {
+        alerts::spawn(&tasks, cfg.alerts.clone(), state.bus.subscribe(), clock.clone());
+    }
+    cluster::spawn_heartbeat(&state);
+    if cfg.synthetic_presence {
+        synthetic::spawn_churn(&state);
+    }
+    if let Some(m) = cfg.mirror.clone() {
+        mirror::spawn(&tasks, m, state.bus.subscribe());
+    }
+    if let Some(sink) = &state.sink {
+        sink.follow_online(&tasks, state.bus.subscribe(), clock.clone());
+        sink.follow_churn(&tasks, state.registry.clone(), clock.clone());
+    }
+    state
+}
 
-    let app = Router::new()
+/// 公共端口路由；`with_admin` 时一并挂载管理接口
+fn app_router(cfg: &config::Config, state: &gateway::AppState, with_admin: bool) -> Router<gateway::AppState> {
+    let mut metrics_routes = Router::new()
+        .route("/v1/metrics/online", get(get_online))
+        .route("/v1/metrics/online/sparkline", get(get_sparkline))
+        .route("/v1/metrics/online/rollups", get(get_rollups))
+        .route("/v1/metrics/online/today", get(get_online_today))
+        .route("/v1/metrics/online/now", get(get_online_now))
+        .route("/v1/metrics/online/window", get(get_online_window))
+        .route("/v1/metrics/referrers", get(get_referrers))
+        .route("/v1/metrics/origins", get(get_origins))
+        .route("/v1/metrics/countries", get(get_countries))
+        .route("/v1/metrics/latency", get(get_latency))
+        .route("/v1/metrics/mirrors", get(get_mirrors))
+        .route("/metrics", get(get_prometheus));
+    if !cfg.public_metrics {
+        metrics_routes = admin::with_scope(metrics_routes, state.clone(), tokens::Scope::ReadPresence);
+    }
+    let mut app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
         .route("/ws", get(ws_web_route))
         .route("/v1/ws", get(ws_web_route))
         .route("/v1/ws/web", get(ws_web_route))
         .route("/web", get(ws_web_route))
-        .route("/v1/metrics/online", get(get_online))
-        .with_state(state);
+        .merge(metrics_routes)
+        .route("/client.js", get(assets::client_js))
+        .route("/client.mjs", get(assets::client_esm))
+        .route("/widget", get(assets::widget));
+    if with_admin {
+        app = app.merge(admin::routes(state.clone(), cfg.synthetic_presence));
+    }
+    app
+}
 
-    let addr: SocketAddr = ([0,0,0,0], cfg.port).into();
-    tracing::info!(%addr, "listening");
-    let listener = tokio::net::TcpListener::bind(addr).await.expect("bind port");
-    axum::serve(listener, app).await.expect("server error");
+/// 请求本机 `/healthz`（默认端口取 `PORT`），成功返回 0
+async fn healthcheck(url: Option<String>) -> i32 {
+    let url = url.unwrap_or_else(|| format!("http://127.0.0.1:{}/healthz", config::Config::from_env().port));
+    match webhook::get(&url).await {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("healthcheck failed: {url}: {e}");
+            1
+        }
+    }
 }
 
-fn log_runtime_env(cfg: &config::Config) {
-    use tracing::info;
-    let allowed = cfg
-        .allowed_origins
-        .as_ref()
-        .map(|s| {
-            let mut v: Vec<_> = s.iter().cloned().collect();
-            v.sort();
-            v.join(",")
-        })
-        .filter(|s| !s.is_empty())
-        .unwrap_or_else(|| "<empty>".to_string());
-    info!(port = cfg.port, ping_interval_secs = cfg.ping_interval.map(|d| d.as_secs()), allowed_origins = %allowed, "startup config");
+/// 存活检查：进程能处理 HTTP 请求即为健康
+async fn healthz() -> &'static str { "ok" }
+
+#[derive(serde::Serialize)]
+struct Readiness {
+    ready: bool,
+    mode: gateway::ServiceMode,
+    tasks: Vec<supervisor::TaskHealth>,
+    /// 处于降载模式（见 `SHED_*`）
+    shedding: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pressure: Option<shed::Pressure>,
 }
 
+/// 就绪检查：服务模式为正常、后台任务均在运行且未降载时 200，否则 503
+async fn readyz(State(state): State<gateway::AppState>) -> (StatusCode, Json<Readiness>) {
+    let mode = state.mode_tx.borrow().clone();
+    let shedding = state.shed.active();
+    let ready = matches!(mode, gateway::ServiceMode::Normal) && state.tasks.all_running() && !shedding;
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let pressure = state.shed.enabled().then(|| state.shed.pressure());
+    (status, Json(Readiness { ready, mode, tasks: state.tasks.health(), shedding, pressure }))
+}
+
+/// 收到 Ctrl+C / SIGTERM 后通知所有连接 `closing{reason:"shutdown"}`，最多等待 2 秒让其断开
+async fn shutdown_signal(state: gateway::AppState) {
+    let ctrl_c = async { let _ = tokio::signal::ctrl_c().await; };
+    #[cfg(unix)]
+    let term = async {
+        if let Ok(mut s) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) { s.recv().await; }
+    };
+    #[cfg(not(unix))]
+    let term = std::future::pending::<()>();
+    tokio::select! { _ = ctrl_c => {}, _ = term => {} }
+
+    tracing::info!(connections = state.registry.len(), "shutting down");
+    state.registry.close_all(events::CloseReason::Shutdown);
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(2);
+    while !state.registry.is_empty() && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    state.meta.flush(state.clock.now_ms()).await;
+}
 
 #[derive(serde::Serialize)]
 struct OnlineCount { online: usize }
 
-async fn get_online(State(state): State<gateway::AppState>) -> Json<OnlineCount> {
-    Json(OnlineCount { online: *state.online_rx.borrow() })
+#[derive(serde::Deserialize)]
+struct OnlineQuery { wait: Option<String>, since_count: Option<usize> }
+
+/// 长轮询上限
+const MAX_LONG_POLL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// 带 `wait` 时挂起请求，直到人数不等于 `since_count`（缺省为当前值）或超时
+async fn get_online(State(state): State<gateway::AppState>, Query(q): Query<OnlineQuery>) -> Result<Json<OnlineCount>, StatusCode> {
+    let Some(wait) = q.wait.as_deref() else {
+        return Ok(Json(OnlineCount { online: state.bus.current().count }));
+    };
+    let wait = config::parse_duration(wait).ok_or(StatusCode::BAD_REQUEST)?.min(MAX_LONG_POLL);
+    let mut rx = state.bus.subscribe();
+    let since = q.since_count.unwrap_or_else(|| rx.borrow_and_update().count);
+    let deadline = tokio::time::Instant::now() + wait;
+    loop {
+        let count = rx.borrow_and_update().count;
+        if count != since { return Ok(Json(OnlineCount { online: count })); }
+        match tokio::time::timeout_at(deadline, rx.changed()).await {
+            Ok(Ok(())) => continue,
+            _ => return Ok(Json(OnlineCount { online: count })),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct OnlineTodayResp {
+    /// 按 `COUNT_MODE` 口径的当日峰值
+    max: usize,
+    #[serde(flatten)]
+    detail: meta::OnlineToday,
+}
+
+async fn get_online_today(State(state): State<gateway::AppState>) -> Json<OnlineTodayResp> {
+    let detail = state.meta.online_today(state.clock.now_ms()).await;
+    let max = match state.count_mode {
+        config::CountMode::Session => detail.max_sessions,
+        config::CountMode::Connection => detail.max_connections,
+        config::CountMode::User => detail.max_users,
+    };
+    Json(OnlineTodayResp { max, detail })
+}
+
+#[derive(serde::Deserialize)]
+struct WindowQuery { minutes: Option<u64> }
+
+#[derive(serde::Serialize)]
+struct OnlineWindow {
+    minutes: u64,
+    /// 窗口内在线过的去重访客数（关联用户的会话按用户合并）
+    visitors: usize,
+    /// 与 `sync` 广播同口径的当前在线人数
+    online: usize,
+}
+
+/// 最近 `minutes` 分钟（默认 5，上限 1440）的去重访客
+async fn get_online_window(State(state): State<gateway::AppState>, Query(q): Query<WindowQuery>) -> Json<OnlineWindow> {
+    let max = meta::VISITOR_WINDOW_MAX.as_secs() / 60;
+    let minutes = q.minutes.unwrap_or(5).clamp(1, max);
+    let since = state.clock.now_ms().saturating_sub(minutes * 60_000);
+    Json(OnlineWindow { minutes, visitors: state.meta.visitors_since(since).await, online: gateway::online_stats(&state).await.count })
+}
+
+#[derive(serde::Deserialize)]
+struct OnlineNowQuery { prefix: Option<String> }
+
+#[derive(serde::Serialize)]
+struct OriginLive { origin: String, live: usize, overflow: usize }
+
+#[derive(serde::Serialize)]
+struct OnlineNow {
+    online: usize,
+    connections: usize,
+    sessions: usize,
+    users: usize,
+    overflow: usize,
+    ts: u64,
+    /// 按来源的当前连接数（含观察者），`prefix` 过滤
+    origins: Vec<OriginLive>,
+}
+
+/// 一次取全站在线汇总与来源分布，无需建立 WS 连接
+async fn get_online_now(State(state): State<gateway::AppState>, Query(q): Query<OnlineNowQuery>) -> Json<OnlineNow> {
+    let s = state.bus.current();
+    let prefix = q.prefix.unwrap_or_default().to_ascii_lowercase();
+    let origins = state
+        .registry
+        .origin_stats(state.clock.now_ms())
+        .into_iter()
+        .filter(|o| o.live > 0 && o.origin.starts_with(&prefix))
+        .map(|o| OriginLive { origin: o.origin, live: o.live, overflow: o.overflow })
+        .collect();
+    Json(OnlineNow {
+        online: s.count,
+        connections: s.connections,
+        sessions: s.sessions,
+        users: s.users,
+        overflow: state.registry.overflow_len(),
+        ts: state.clock.now_ms(),
+        origins,
+    })
+}
+
+async fn get_referrers(State(state): State<gateway::AppState>) -> Json<meta::ReferrerBreakdown> {
+    Json(state.meta.referrer_breakdown().await)
+}
+
+#[derive(serde::Deserialize)]
+struct SparklineQuery { window: Option<String>, points: Option<usize> }
+
+async fn get_sparkline(State(state): State<gateway::AppState>, Query(q): Query<SparklineQuery>) -> Result<Json<history::Sparkline>, StatusCode> {
+    let window = match q.window.as_deref() {
+        Some(w) => config::parse_duration(w).filter(|d| !d.is_zero()).ok_or(StatusCode::BAD_REQUEST)?,
+        None => std::time::Duration::from_secs(3600),
+    };
+    Ok(Json(state.history.sparkline(window, q.points.unwrap_or(60), state.clock.now_ms())))
+}
+
+#[derive(serde::Deserialize)]
+struct RollupQuery { #[serde(default)] granularity: history::Granularity, limit: Option<usize> }
+
+async fn get_rollups(State(state): State<gateway::AppState>, Query(q): Query<RollupQuery>) -> Json<Vec<history::Rollup>> {
+    Json(state.history.rollups(q.granularity, q.limit.unwrap_or(48).min(10_000)))
+}
+
+/// Prometheus 文本格式：WS 收发计数 / 字节 / 帧错误，以及在线人数与 RTT 分位
+async fn get_prometheus(State(state): State<gateway::AppState>) -> impl axum::response::IntoResponse {
+    let s = state.bus.current();
+    let lat = state.registry.latency();
+    let mut gauges = vec![
+        ("activenow_online", "", s.count as f64),
+        ("activenow_connections", "", s.connections as f64),
+        ("activenow_sessions", "", s.sessions as f64),
+        ("activenow_users", "", s.users as f64),
+        ("activenow_ws_live", "", state.registry.len() as f64),
+        ("activenow_ws_overflow", "", state.registry.overflow_len() as f64),
+        ("activenow_sync_paused", "", if state.bus.paused() { 1.0 } else { 0.0 }),
+        ("activenow_shedding", "", if state.shed.active() { 1.0 } else { 0.0 }),
+    ];
+    if state.shed.enabled() {
+        let p = state.shed.pressure();
+        if let Some(v) = p.rss_mb { gauges.push(("activenow_rss_mb", "", v as f64)); }
+        gauges.push(("activenow_sched_lag_ms", "", p.lag_ms as f64));
+        gauges.push(("activenow_runtime_queue_depth", "", p.queue_depth as f64));
+    }
+    if let Some(v) = lat.p50_ms { gauges.push(("activenow_rtt_ms", "quantile=\"0.5\"", v as f64)); }
+    if let Some(v) = lat.p95_ms { gauges.push(("activenow_rtt_ms", "quantile=\"0.95\"", v as f64)); }
+    ([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")], state.metrics.render(&gauges))
+}
+
+async fn get_latency(State(state): State<gateway::AppState>) -> Json<registry::LatencyStats> {
+    Json(state.registry.latency())
+}
+
+/// 镜像模式下各下游实例的最近上报（已计入在线人数）
+async fn get_mirrors(State(state): State<gateway::AppState>) -> Json<Vec<mirror::MirrorNode>> {
+    Json(mirror::list(&state))
+}
+
+/// 按国家的在线分布；未配置 `GEO_HEADER` 时 404
+async fn get_countries(State(state): State<gateway::AppState>) -> Result<Json<events::Geo>, StatusCode> {
+    if state.geo_header.is_none() { return Err(StatusCode::NOT_FOUND); }
+    Ok(Json(state.registry.country_breakdown(state.clock.now_ms())))
+}
+
+async fn get_origins(State(state): State<gateway::AppState>) -> Json<Vec<registry::OriginStats>> {
+    Json(state.registry.origin_stats(state.clock.now_ms()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::CountMode;
+    use crate::test_support::{self, TestServer};
+
+    /// 窗口接口的 `online` 与广播同口径（`COUNT_MODE=connection` 时按连接计）
+    #[tokio::test]
+    async fn online_window_follows_count_mode() {
+        let mut cfg = test_support::config();
+        cfg.count_mode = CountMode::Connection;
+        let server = TestServer::start(cfg).await;
+        let _a = server.connect("socket_session_id=s1").await;
+        let _b = server.connect("socket_session_id=s1").await;
+        let (status, body) = server.http("GET", "/v1/metrics/online/window?minutes=5", None).await;
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""visitors":1"#) && body.contains(r#""online":2"#), "{body}");
+    }
 }
@@ -1,43 +1,509 @@
+
+use std::{collections::{HashMap, HashSet}, path::PathBuf, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}, time::Duration};
+
 use async_trait::async_trait;
-use dashmap::DashMap;
+use dashmap::{mapref::entry::Entry, DashMap};
 use serde::{Deserialize, Serialize};
 
+use crate::config::StatsZone;
+use crate::ipfilter;
+use crate::registry::ConnInfo;
+use crate::supervisor::Supervisor;
+use crate::time::SharedClock;
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SocketMetadata {
     pub identity: String,
     pub session_id: String,
+    #[serde(default)]
+    pub attribution: Attribution,
+    /// 登录后关联的用户标识
+    #[serde(default)]
+    pub user_id: Option<String>,
+}
+
+impl SocketMetadata {
+    /// 去重访客键：已关联用户按用户，否则按会话
+    fn visitor_key(&self) -> String {
+        match &self.user_id {
+            Some(u) => format!("u:{u}"),
+            None => format!("s:{}", self.session_id),
+        }
+    }
+}
+
+/// 流量来源：连接时的 Referer 与 `utm_*` 参数
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attribution {
+    /// 来源主机名（小写，已去除 `www.`）；直接访问为 `None`
+    pub referrer: Option<String>,
+    pub utm_source: Option<String>,
+    pub utm_medium: Option<String>,
+    pub utm_campaign: Option<String>,
+    pub utm_term: Option<String>,
+    pub utm_content: Option<String>,
+}
+
+/// 来源分布（按在线会话数降序）
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReferrerBreakdown {
+    pub referrers: Vec<SourceCount>,
+    pub utm_sources: Vec<SourceCount>,
+    pub utm_campaigns: Vec<SourceCount>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceCount {
+    pub source: String,
+    pub count: usize,
+}
+
+/// 封禁对象类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BanTarget {
+    Session,
+    Ip,
+}
+
+/// 封禁记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    pub target: BanTarget,
+    /// session_id，或 IP / 网段（与 `IP_V4_PREFIX` / `IP_V6_PREFIX` 的聚合结果一致，如 `2001:db8:1:2::/64`）
+    pub value: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub created_ms: u64,
+    /// `None` 为永久
+    #[serde(default)]
+    pub expires_ms: Option<u64>,
+}
+
+impl Ban {
+    pub fn active(&self, now_ms: u64) -> bool { self.expires_ms.is_none_or(|t| t > now_ms) }
+
+    /// 存取用的键：IP 封禁按 `ipfilter::normalize` 规范化，`::ffff:1.2.3.4` 与 `1.2.3.4` 视为同一地址
+    pub fn key(target: BanTarget, value: &str) -> (BanTarget, String) {
+        match target {
+            BanTarget::Ip => (target, ipfilter::normalize(value)),
+            BanTarget::Session => (target, value.to_string()),
+        }
+    }
+
+    /// 是否命中该在线连接
+    pub fn hits(&self, c: &ConnInfo) -> bool {
+        match self.target {
+            BanTarget::Session => c.session_id == self.value,
+            BanTarget::Ip => {
+                let value = ipfilter::normalize(&self.value);
+                c.remote_ip.as_deref().is_some_and(|ip| ipfilter::normalize(ip) == value) || c.subnet == value
+            }
+        }
+    }
+}
+
+/// 当日在线统计（按 `STATS_TIMEZONE` 的日期滚动）
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OnlineToday {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub max_sessions: usize,
+    pub max_connections: usize,
+    /// 按用户合并后的访客峰值
+    pub max_users: usize,
+    /// 当日出现过的去重会话数
+    pub unique_sessions: usize,
 }
 
 #[async_trait]
 pub trait MetaStore: Send + Sync {
     async fn upsert_identity(&self, sid: &str, session_id: String, now_ms: u64);
     async fn set_session_id(&self, sid: &str, session_id: String, now_ms: u64);
-    async fn clear(&self, sid: &str);
+    async fn set_attribution(&self, sid: &str, attribution: Attribution);
+    async fn clear(&self, sid: &str, now_ms: u64);
+    async fn get(&self, sid: &str) -> Option<SocketMetadata>;
+    /// 会话下仍持有元数据的连接 `sid`（含断线宽限期内的）
+    async fn sids_for_session(&self, session_id: &str) -> Vec<String>;
     async fn unique_session_count(&self) -> usize;
+    async fn connection_count(&self) -> usize;
+    /// 去重访客数（关联了用户的会话按用户合并）
+    async fn unique_user_count(&self) -> usize;
+    /// `since_ms` 之后在线过的去重访客数（口径同 `unique_user_count`，当前在线的均计入）；最多回溯 `VISITOR_WINDOW_MAX`
+    async fn visitors_since(&self, since_ms: u64) -> usize;
+    /// 将连接关联到用户；连接不存在时返回 `false`
+    async fn link_user(&self, sid: &str, user_id: String, now_ms: u64) -> bool;
+    async fn referrer_breakdown(&self) -> ReferrerBreakdown;
+    async fn online_today(&self, now_ms: u64) -> OnlineToday;
+    /// 当日统计完整快照（含会话集合），用于迁移导出
+    async fn daily_snapshot(&self, now_ms: u64) -> DailyState;
+    /// 合并同日快照；日期不是今天时忽略并返回 `false`
+    async fn merge_daily(&self, state: DailyState, now_ms: u64) -> bool;
+    async fn add_ban(&self, ban: Ban);
+    async fn remove_ban(&self, target: BanTarget, value: &str) -> bool;
+    async fn list_bans(&self, now_ms: u64) -> Vec<Ban>;
+    /// 返回首个命中且未过期的封禁
+    async fn find_ban(&self, candidates: &[(BanTarget, &str)], now_ms: u64) -> Option<Ban>;
+    /// 登记实例心跳，同一节点覆盖
+    async fn heartbeat(&self, hb: NodeHeartbeat);
+    /// 最近 `NODE_TTL` 内有心跳的实例，按节点名排序
+    async fn nodes(&self, now_ms: u64) -> Vec<NodeHeartbeat>;
+    /// 退出前落盘待写数据
+    async fn flush(&self, _now_ms: u64) {}
+}
+
+/// 超过该时长没有心跳的实例从列表移除
+pub const NODE_TTL: Duration = Duration::from_secs(60);
+
+/// 实例心跳
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeHeartbeat {
+    pub node: String,
+    /// 镜像节点未上报版本时为 `None`
+    pub version: Option<String>,
+    pub connections: usize,
+    pub online: usize,
+    /// 服务模式（`normal` / `maintenance` / `drain`）；镜像节点为 `None`
+    pub mode: Option<String>,
+    /// 经镜像上报登记（而非本实例自身心跳）
+    pub via_mirror: bool,
+    pub last_seen_ms: u64,
 }
 
+/// 滑动窗口访客数的最大回溯
+pub const VISITOR_WINDOW_MAX: Duration = Duration::from_secs(24 * 3600);
+
 // ---------------------- Memory backend ----------------------
 
 #[derive(Clone, Default)]
 pub struct MemoryMetaStore {
     inner: DashMap<String, SocketMetadata>,
+    /// session_id -> 连接数（引用计数；归零时移除）
+    sessions: DashMap<String, usize>,
+    /// 访客键（见 `SocketMetadata::visitor_key`）-> 连接数
+    visitors: DashMap<String, usize>,
+    /// 已离开的访客键 -> 最后在线时刻（毫秒），保留 `VISITOR_WINDOW_MAX`
+    departed: DashMap<String, u64>,
+    last_prune_ms: Arc<AtomicU64>,
+    daily: Arc<Mutex<DailyState>>,
+    bans: DashMap<(BanTarget, String), Ban>,
+    /// 封禁持久化文件（`BANS_FILE`），每次变更后整体重写
+    bans_file: Option<PathBuf>,
+    /// 当日统计持久化文件（`DAILY_STATS_FILE`）
+    daily_file: Option<PathBuf>,
+    /// 日统计换日所用的时区（`STATS_TIMEZONE`）
+    zone: StatsZone,
+    nodes: DashMap<String, NodeHeartbeat>,
+}
+
+/// 当日统计的内部状态，亦为 `DAILY_STATS_FILE` 与迁移包的持久化格式
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DailyState {
+    pub date: String,
+    pub max_sessions: usize,
+    pub max_connections: usize,
+    #[serde(default)]
+    pub max_users: usize,
+    pub sessions: HashSet<String>,
+    /// 写入时刻（毫秒）
+    #[serde(default)]
+    pub saved_ms: u64,
+}
+
+impl DailyState {
+    /// 峰值取大、会话取并集，重复合并同一快照结果不变
+    fn merge(&mut self, other: DailyState) {
+        self.max_sessions = self.max_sessions.max(other.max_sessions);
+        self.max_connections = self.max_connections.max(other.max_connections);
+        self.max_users = self.max_users.max(other.max_users);
+        self.sessions.extend(other.sessions);
+        self.saved_ms = self.saved_ms.max(other.saved_ms);
+    }
+}
+
+/// 先写临时文件再重命名，避免进程中途退出留下半截文件
+async fn write_atomic(path: &std::path::Path, raw: Vec<u8>) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    tokio::fs::write(&tmp, raw).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+impl MemoryMetaStore {
+    pub fn new() -> Self { Self::default() }
+
+    /// 启用封禁持久化并加载已有记录
+    pub fn with_bans_file(mut self, path: PathBuf) -> Self {
+        match std::fs::read_to_string(&path) {
+            Ok(raw) => match serde_json::from_str::<Vec<Ban>>(&raw) {
+                Ok(bans) => for b in bans { self.insert_ban(b); },
+                Err(e) => tracing::warn!(error = %e, path = %path.display(), "invalid bans file"),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!(error = %e, path = %path.display(), "read bans file failed"),
+        }
+        self.bans_file = Some(path);
+        self
+    }
+
+    fn insert_ban(&self, mut ban: Ban) {
+        let key = Ban::key(ban.target, &ban.value);
+        ban.value = key.1.clone();
+        self.bans.insert(key, ban);
+    }
+
+    async fn save_bans(&self) {
+        let Some(path) = &self.bans_file else { return };
+        let all: Vec<Ban> = self.bans.iter().map(|b| b.clone()).collect();
+        if let Err(e) = write_atomic(path, serde_json::to_vec(&all).unwrap_or_default()).await {
+            tracing::warn!(error = %e, path = %path.display(), "save bans failed");
+        }
+    }
+
+    /// 跨日时重置；记录会话并刷新当日峰值
+    fn track_daily(&self, session_id: Option<&str>, now_ms: u64) {
+        let mut d = self.daily.lock().unwrap();
+        let today = self.today(now_ms);
+        if d.date != today { *d = DailyState { date: today, ..Default::default() }; }
+        if let Some(s) = session_id { if !d.sessions.contains(s) { d.sessions.insert(s.to_string()); } }
+        d.max_sessions = d.max_sessions.max(self.sessions.len());
+        d.max_connections = d.max_connections.max(self.inner.len());
+        d.max_users = d.max_users.max(self.visitors.len());
+    }
+
+    /// 日统计按 `zone` 的本地零点换日；需在 `with_daily_file` 之前调用
+    pub fn with_timezone(mut self, zone: StatsZone) -> Self {
+        self.zone = zone;
+        self
+    }
+
+    fn today(&self, now_ms: u64) -> String { local_date(now_ms, self.zone.offset_at(now_ms)) }
+
+    /// 启用当日统计持久化，并合并同日的已有快照；文件缺失或日期不同则忽略
+    pub fn with_daily_file(mut self, path: PathBuf, now_ms: u64) -> Self {
+        if let Ok(raw) = std::fs::read_to_string(&path) {
+            match serde_json::from_str::<DailyState>(&raw) {
+                Ok(state) if state.date == self.today(now_ms) => {
+                    let mut d = self.daily.lock().unwrap();
+                    if d.date != state.date { *d = DailyState { date: state.date.clone(), ..Default::default() }; }
+                    d.merge(state);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, path = %path.display(), "invalid daily stats file"),
+            }
+        }
+        self.daily_file = Some(path);
+        self
+    }
+
+    async fn save_daily(&self, now_ms: u64) {
+        let Some(path) = &self.daily_file else { return };
+        let raw = {
+            let mut d = self.daily.lock().unwrap();
+            d.saved_ms = now_ms;
+            serde_json::to_vec(&*d).unwrap_or_default()
+        };
+        if let Err(e) = write_atomic(path, raw).await {
+            tracing::warn!(error = %e, path = %path.display(), "save daily stats failed");
+        }
+    }
+
+    /// 按 `every` 定期写入当日统计
+    pub fn spawn_daily_persist(self: Arc<Self>, tasks: &Arc<Supervisor>, every: Duration, clock: SharedClock) {
+        if self.daily_file.is_none() { return; }
+        tasks.spawn("daily_persist", move || {
+            let (this, clock) = (self.clone(), clock.clone());
+            async move {
+                let mut tick = tokio::time::interval(every);
+                tick.tick().await;
+                loop {
+                    tick.tick().await;
+                    this.save_daily(clock.now_ms()).await;
+                }
+            }
+        });
+    }
+
+    /// 访客键引用归零时记录离开时刻；每分钟清理一次超出窗口的记录
+    fn release_visitor(&self, key: &str, now_ms: u64) {
+        release(&self.visitors, key);
+        if self.visitors.contains_key(key) { return; }
+        self.departed.insert(key.to_string(), now_ms);
+        let last = self.last_prune_ms.load(Ordering::Relaxed);
+        if now_ms.saturating_sub(last) >= 60_000 && self.last_prune_ms.compare_exchange(last, now_ms, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            let cutoff = now_ms.saturating_sub(VISITOR_WINDOW_MAX.as_millis() as u64);
+            self.departed.retain(|_, t| *t >= cutoff);
+        }
+    }
+
+    fn replace_session(&self, sid: &str, session_id: String, now_ms: u64) -> bool {
+        let (old, old_key, new_key) = match self.inner.get_mut(sid) {
+            Some(mut ent) if ent.session_id != session_id => {
+                let old_key = ent.visitor_key();
+                let old = std::mem::replace(&mut ent.session_id, session_id.clone());
+                (old, old_key, ent.visitor_key())
+            }
+            Some(_) => return true,
+            None => return false,
+        };
+        release(&self.sessions, &old);
+        retain(&self.sessions, &session_id);
+        retain(&self.visitors, &new_key);
+        self.release_visitor(&old_key, now_ms);
+        true
+    }
 }
 
-impl MemoryMetaStore { pub fn new() -> Self { Self::default() } }
+/// 引用计数 +1
+fn retain(map: &DashMap<String, usize>, key: &str) {
+    *map.entry(key.to_string()).or_insert(0) += 1;
+}
+
+/// 引用计数 -1，归零时移除
+fn release(map: &DashMap<String, usize>, key: &str) {
+    if let Entry::Occupied(mut e) = map.entry(key.to_string()) {
+        *e.get_mut() -= 1;
+        if *e.get() == 0 { e.remove(); }
+    }
+}
 
 #[async_trait]
 impl MetaStore for MemoryMetaStore {
-    async fn upsert_identity(&self, sid: &str, session_id: String, _now_ms: u64) {
-        self.inner
-            .entry(sid.to_string())
-            .and_modify(|m| { m.session_id = session_id.clone(); })
-            .or_insert_with(|| SocketMetadata { identity: sid.to_string(), session_id });
+    async fn upsert_identity(&self, sid: &str, session_id: String, now_ms: u64) {
+        if !self.replace_session(sid, session_id.clone(), now_ms) {
+            let meta = SocketMetadata { identity: sid.to_string(), session_id: session_id.clone(), ..Default::default() };
+            retain(&self.sessions, &session_id);
+            retain(&self.visitors, &meta.visitor_key());
+            self.inner.insert(sid.to_string(), meta);
+        }
+        self.track_daily(Some(&session_id), now_ms);
+    }
+    async fn set_session_id(&self, sid: &str, session_id: String, now_ms: u64) {
+        if self.replace_session(sid, session_id.clone(), now_ms) { self.track_daily(Some(&session_id), now_ms); }
+    }
+    async fn set_attribution(&self, sid: &str, attribution: Attribution) {
+        if let Some(mut ent) = self.inner.get_mut(sid) { ent.attribution = attribution; }
+    }
+    async fn clear(&self, sid: &str, now_ms: u64) {
+        if let Some((_, m)) = self.inner.remove(sid) {
+            release(&self.sessions, &m.session_id);
+            self.release_visitor(&m.visitor_key(), now_ms);
+        }
+    }
+    async fn get(&self, sid: &str) -> Option<SocketMetadata> { self.inner.get(sid).map(|m| m.clone()) }
+    async fn sids_for_session(&self, session_id: &str) -> Vec<String> {
+        if !self.sessions.contains_key(session_id) { return Vec::new(); }
+        self.inner.iter().filter(|m| m.session_id == session_id).map(|m| m.key().clone()).collect()
+    }
+    async fn unique_session_count(&self) -> usize { self.sessions.len() }
+    async fn connection_count(&self) -> usize { self.inner.len() }
+    async fn unique_user_count(&self) -> usize { self.visitors.len() }
+    async fn visitors_since(&self, since_ms: u64) -> usize {
+        let left = self.departed.iter().filter(|e| *e.value() >= since_ms && !self.visitors.contains_key(e.key())).count();
+        self.visitors.len() + left
+    }
+    async fn link_user(&self, sid: &str, user_id: String, now_ms: u64) -> bool {
+        let (old_key, new_key) = match self.inner.get_mut(sid) {
+            Some(mut ent) => {
+                let old_key = ent.visitor_key();
+                ent.user_id = Some(user_id);
+                (old_key, ent.visitor_key())
+            }
+            None => return false,
+        };
+        if old_key != new_key {
+            retain(&self.visitors, &new_key);
+            self.release_visitor(&old_key, now_ms);
+        }
+        self.track_daily(None, now_ms);
+        true
+    }
+    async fn referrer_breakdown(&self) -> ReferrerBreakdown {
+        // 同一会话多个连接只计一次（取首个遇到的来源）
+        let mut by_session: HashMap<String, Attribution> = HashMap::new();
+        for v in self.inner.iter() {
+            by_session.entry(v.session_id.clone()).or_insert_with(|| v.attribution.clone());
+        }
+        let tally = |f: &dyn Fn(&Attribution) -> Option<String>| {
+            let mut m: HashMap<String, usize> = HashMap::new();
+            for a in by_session.values() { *m.entry(f(a).unwrap_or_else(|| "(direct)".to_string())).or_default() += 1; }
+            let mut v: Vec<_> = m.into_iter().map(|(source, count)| SourceCount { source, count }).collect();
+            v.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.source.cmp(&b.source)));
+            v
+        };
+        ReferrerBreakdown {
+            referrers: tally(&|a| a.referrer.clone()),
+            utm_sources: tally(&|a| a.utm_source.clone()),
+            utm_campaigns: tally(&|a| a.utm_campaign.clone()),
+        }
+    }
+    async fn online_today(&self, now_ms: u64) -> OnlineToday {
+        self.track_daily(None, now_ms);
+        let d = self.daily.lock().unwrap();
+        OnlineToday { date: d.date.clone(), max_sessions: d.max_sessions, max_connections: d.max_connections, max_users: d.max_users, unique_sessions: d.sessions.len() }
+    }
+    async fn daily_snapshot(&self, now_ms: u64) -> DailyState {
+        self.track_daily(None, now_ms);
+        self.daily.lock().unwrap().clone()
     }
-    async fn set_session_id(&self, sid: &str, session_id: String, _now_ms: u64) {
-        if let Some(mut ent) = self.inner.get_mut(sid) { ent.session_id = session_id; }
+    async fn merge_daily(&self, state: DailyState, now_ms: u64) -> bool {
+        self.track_daily(None, now_ms);
+        {
+            let mut d = self.daily.lock().unwrap();
+            if d.date != state.date { return false; }
+            d.merge(state);
+        }
+        self.save_daily(now_ms).await;
+        true
     }
-    async fn clear(&self, sid: &str) { self.inner.remove(sid); }
-    async fn unique_session_count(&self) -> usize {
-        use std::collections::HashSet; let mut set = HashSet::new(); for v in self.inner.iter() { set.insert(v.session_id.clone()); } set.len()
+    async fn flush(&self, now_ms: u64) {
+        self.save_daily(now_ms).await;
     }
+    async fn add_ban(&self, ban: Ban) {
+        self.insert_ban(ban);
+        self.save_bans().await;
+    }
+    async fn remove_ban(&self, target: BanTarget, value: &str) -> bool {
+        let removed = self.bans.remove(&Ban::key(target, value)).is_some();
+        if removed { self.save_bans().await; }
+        removed
+    }
+    async fn list_bans(&self, now_ms: u64) -> Vec<Ban> {
+        let before = self.bans.len();
+        self.bans.retain(|_, b| b.active(now_ms));
+        if self.bans.len() < before { self.save_bans().await; }
+        let mut v: Vec<Ban> = self.bans.iter().map(|b| b.clone()).collect();
+        v.sort_by(|a, b| a.created_ms.cmp(&b.created_ms).then_with(|| a.value.cmp(&b.value)));
+        v
+    }
+    async fn find_ban(&self, candidates: &[(BanTarget, &str)], now_ms: u64) -> Option<Ban> {
+        candidates.iter().find_map(|(t, v)| self.bans.get(&Ban::key(*t, v)).filter(|b| b.active(now_ms)).map(|b| b.clone()))
+    }
+
+    async fn heartbeat(&self, hb: NodeHeartbeat) {
+        self.nodes.insert(hb.node.clone(), hb);
+    }
+
+    async fn nodes(&self, now_ms: u64) -> Vec<NodeHeartbeat> {
+        let cutoff = now_ms.saturating_sub(NODE_TTL.as_millis() as u64);
+        self.nodes.retain(|_, n| n.last_seen_ms >= cutoff);
+        let mut v: Vec<_> = self.nodes.iter().map(|n| n.clone()).collect();
+        v.sort_by(|a, b| a.node.cmp(&b.node));
+        v
+    }
+}
+
+/// Unix 毫秒 -> 偏移 `offset_secs` 处的日期 `YYYY-MM-DD`
+fn local_date(now_ms: u64, offset_secs: i64) -> String {
+    // Howard Hinnant 的 civil_from_days
+    let z = (now_ms as i64 + offset_secs * 1000).div_euclid(86_400_000) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!("{y:04}-{m:02}-{d:02}")
 }
@@ -0,0 +1,78 @@
+use std::{fmt::Write, sync::Arc};
+
+use dashmap::DashMap;
+
+/// 进程内计数器，以 Prometheus 文本格式导出（`GET /metrics`）
+#[derive(Default)]
+pub struct Metrics {
+    /// (指标名, 标签串) -> 累计值
+    counters: DashMap<(&'static str, String), u64>,
+}
+
+impl Metrics {
+    pub fn add(&self, name: &'static str, labels: String, n: u64) {
+        *self.counters.entry((name, labels)).or_default() += n;
+    }
+
+    /// 绑定到 WS 入口（如 `ws`、`v1/ws/web`）的计数句柄
+    pub fn conn(self: &Arc<Self>, endpoint: &'static str) -> ConnMetrics {
+        ConnMetrics { metrics: self.clone(), endpoint }
+    }
+
+    /// 计数器与调用方给出的瞬时值（gauge）一并渲染
+    pub fn render(&self, gauges: &[(&str, &str, f64)]) -> String {
+        let mut rows: Vec<(&'static str, String, u64)> = self.counters.iter().map(|e| (e.key().0, e.key().1.clone(), *e.value())).collect();
+        rows.sort();
+        let mut out = String::new();
+        let mut last = "";
+        for (name, labels, v) in &rows {
+            if *name != last {
+                let _ = writeln!(out, "# TYPE {name} counter");
+                last = name;
+            }
+            let _ = writeln!(out, "{name}{{{labels}}} {v}");
+        }
+        let mut last = "";
+        for (name, labels, v) in gauges {
+            if *name != last {
+                let _ = writeln!(out, "# TYPE {name} gauge");
+                last = name;
+            }
+            if labels.is_empty() { let _ = writeln!(out, "{name} {v}"); } else { let _ = writeln!(out, "{name}{{{labels}}} {v}"); }
+        }
+        out
+    }
+}
+
+/// 单个连接使用的计数句柄
+#[derive(Clone)]
+pub struct ConnMetrics {
+    metrics: Arc<Metrics>,
+    endpoint: &'static str,
+}
+
+impl ConnMetrics {
+    pub fn msg_in(&self, kind: &str, bytes: usize) {
+        self.metrics.add("activenow_ws_messages_total", format!("endpoint=\"{}\",direction=\"in\",type=\"{kind}\"", self.endpoint), 1);
+        self.metrics.add("activenow_ws_bytes_total", format!("endpoint=\"{}\",direction=\"in\"", self.endpoint), bytes as u64);
+    }
+
+    pub fn msg_out(&self, kind: &str, bytes: usize) {
+        self.metrics.add("activenow_ws_messages_total", format!("endpoint=\"{}\",direction=\"out\",type=\"{kind}\"", self.endpoint), 1);
+        self.metrics.add("activenow_ws_bytes_total", format!("endpoint=\"{}\",direction=\"out\"", self.endpoint), bytes as u64);
+    }
+
+    /// 被拒绝的上行帧：`too_large` / `binary_unsupported` / `invalid_message`
+    pub fn frame_error(&self, code: &str) {
+        self.metrics.add("activenow_ws_frame_errors_total", format!("endpoint=\"{}\",code=\"{code}\"", self.endpoint), 1);
+    }
+
+    /// 慢客户端积压时被新帧取代或挤出的下行消息
+    pub fn dropped(&self, kind: &str) {
+        self.metrics.add("activenow_ws_dropped_total", format!("endpoint=\"{}\",type=\"{kind}\"", self.endpoint), 1);
+    }
+
+    pub fn accepted(&self) {
+        self.metrics.add("activenow_ws_connections_total", format!("endpoint=\"{}\"", self.endpoint), 1);
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use activenow_protocol::{Client, ClientMsg};
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::cluster::{self, HEARTBEAT_EVERY};
+use crate::gateway::{AppState, OnlineStats};
+use crate::meta::NodeHeartbeat;
+use crate::supervisor::Supervisor;
+
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// 镜像上游（`MIRROR_UPSTREAM`）
+#[derive(Debug, Clone)]
+pub struct MirrorConfig {
+    /// 上游实例的 WS 地址，如 `ws://global:8080/v1/ws/web`
+    pub upstream: String,
+    /// 本实例在上游的名称（`MIRROR_NODE`）
+    pub node: String,
+    /// 上游签发的 `write:presence` 令牌（`MIRROR_TOKEN`）
+    pub token: Option<String>,
+}
+
+/// 上游侧：某个下游实例最近一次上报
+#[derive(Debug, Clone, Serialize)]
+pub struct MirrorNode {
+    pub node: String,
+    /// 上报所用连接
+    #[serde(skip)]
+    pub sid: String,
+    #[serde(flatten)]
+    pub stats: OnlineStats,
+    pub updated_ms: u64,
+}
+
+/// 记录下游上报并登记其心跳；同名节点以最新连接为准
+pub async fn accept(state: &AppState, sid: &str, node: String, stats: OnlineStats, version: Option<String>) {
+    let node = node.trim().chars().take(64).collect::<String>();
+    if node.is_empty() { return; }
+    let now = state.clock.now_ms();
+    let entry = MirrorNode { node: node.clone(), sid: sid.to_string(), stats, updated_ms: now };
+    state.mirrors.insert(node.clone(), entry);
+    let version = version.map(|v| v.chars().take(32).collect());
+    let hb = NodeHeartbeat { node, version, connections: stats.connections, online: stats.count, mode: None, via_mirror: true, last_seen_ms: now };
+    state.meta.heartbeat(hb).await;
+}
+
+/// 连接断开时移除其上报的节点；有移除时返回 true
+pub fn release(state: &AppState, sid: &str) -> bool {
+    let before = state.mirrors.len();
+    state.mirrors.retain(|_, m| m.sid != sid);
+    state.mirrors.len() != before
+}
+
+/// 各下游节点统计之和
+pub fn totals(state: &AppState) -> OnlineStats {
+    state.mirrors.iter().fold(OnlineStats::default(), |acc, m| OnlineStats {
+        count: acc.count + m.stats.count,
+        connections: acc.connections + m.stats.connections,
+        sessions: acc.sessions + m.stats.sessions,
+        users: acc.users + m.stats.users,
+    })
+}
+
+/// 按节点名排序
+pub fn list(state: &AppState) -> Vec<MirrorNode> {
+    let mut v: Vec<_> = state.mirrors.iter().map(|m| m.clone()).collect();
+    v.sort_by(|a, b| a.node.cmp(&b.node));
+    v
+}
+
+/// 下游侧：以观察者身份连接上游，在线统计变化时上报（无变化时按心跳间隔重报）；断线后按退避重连
+pub fn spawn(tasks: &Arc<Supervisor>, cfg: MirrorConfig, rx: watch::Receiver<OnlineStats>) {
+    tasks.spawn("mirror_upstream", move || {
+        let (cfg, mut rx) = (cfg.clone(), rx.clone());
+        async move {
+            let sep = if cfg.upstream.contains('?') { '&' } else { '?' };
+            let mut url = format!("{}{sep}observe=1&events=error", cfg.upstream);
+            if let Some(token) = &cfg.token { url.push_str(&format!("&token={token}")); }
+            let mut backoff = BASE_BACKOFF;
+            loop {
+                let started = tokio::time::Instant::now();
+                match forward(&url, &cfg.node, &mut rx).await {
+                    Ok(()) => return,
+                    Err(e) => tracing::warn!(upstream = %cfg.upstream, error = %e, retry_in_ms = backoff.as_millis() as u64, "mirror upstream disconnected"),
+                }
+                if started.elapsed() >= MAX_BACKOFF { backoff = BASE_BACKOFF; }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    });
+}
+
+/// 单次连接；本地统计源关闭时返回 `Ok`
+async fn forward(url: &str, node: &str, rx: &mut watch::Receiver<OnlineStats>) -> Result<(), String> {
+    let (mut client, _) = Client::connect(url).await.map_err(|e| e.to_string())?;
+    tracing::info!(node, "mirror upstream connected");
+    let report = |s: OnlineStats| ClientMsg::Mirror { node: node.to_string(), count: s.count, connections: s.connections, sessions: s.sessions, users: s.users, version: Some(cluster::version()) };
+    let mut keepalive = tokio::time::interval(HEARTBEAT_EVERY);
+    loop {
+        tokio::select! {
+            changed = rx.changed() => {
+                if changed.is_err() { return Ok(()); }
+                let stats = *rx.borrow_and_update();
+                client.send(&report(stats)).await.map_err(|e| e.to_string())?;
+                keepalive.reset();
+            }
+            _ = keepalive.tick() => {
+                let stats = *rx.borrow_and_update();
+                client.send(&report(stats)).await.map_err(|e| e.to_string())?;
+            }
+            msg = client.next() => match msg {
+                Some(Ok(activenow_protocol::ServerMsg::Error(e))) => return Err(format!("rejected: {}", e.code)),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.to_string()),
+                None => return Err("closed".to_string()),
+            },
+        }
+    }
+}
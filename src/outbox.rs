@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use axum::extract::ws::{Message, WebSocket};
+use futures_util::{stream::SplitSink, SinkExt};
+use tokio::sync::Notify;
+
+use crate::gateway::frame_len;
+use crate::metrics::ConnMetrics;
+use crate::registry::ConnRegistry;
+
+/// 控制帧积压超过该数量视为客户端已失去响应
+const CONTROL_MAX: usize = 64;
+/// 低优先级事件的积压上限，超出时丢弃最旧的
+const EVENT_MAX: usize = 16;
+
+/// 下行优先级：控制帧（ping / error / closing / close）> 人数（`sync` / `delta`）> 其余事件（如 `geo`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// 从不丢弃
+    Control,
+    /// 只保留最新一帧
+    Count,
+    /// 积压时最先丢弃
+    Event,
+}
+
+#[derive(Default)]
+struct Queues {
+    control: VecDeque<(&'static str, Message)>,
+    count: Option<(&'static str, Message)>,
+    events: VecDeque<(&'static str, Message)>,
+    closed: bool,
+}
+
+/// 单连接发送队列：由独立写任务按优先级取出，慢客户端只积压、不阻塞连接主循环
+#[derive(Clone)]
+pub struct Outbox {
+    queues: Arc<Mutex<Queues>>,
+    notify: Arc<Notify>,
+    metrics: ConnMetrics,
+}
+
+impl Outbox {
+    /// 启动写任务；写失败或队列关闭且清空后任务结束
+    pub fn spawn(mut sink: SplitSink<WebSocket, Message>, metrics: ConnMetrics, registry: Arc<ConnRegistry>, sid: String) -> (Self, tokio::task::JoinHandle<()>) {
+        let this = Self { queues: Default::default(), notify: Default::default(), metrics };
+        let out = this.clone();
+        let task = tokio::spawn(async move {
+            while let Some((kind, prio, msg)) = out.next().await {
+                out.metrics.msg_out(kind, frame_len(&msg));
+                if sink.send(msg).await.is_err() { break; }
+                if prio != Priority::Control { registry.on_outbound(&sid); }
+            }
+            out.queues.lock().unwrap().closed = true;
+        });
+        (this, task)
+    }
+
+    /// 入队；写任务已结束或控制帧积压过多时返回 false
+    pub fn push(&self, prio: Priority, kind: &'static str, msg: Message) -> bool {
+        let mut q = self.queues.lock().unwrap();
+        if q.closed { return false; }
+        match prio {
+            Priority::Control => {
+                if q.control.len() >= CONTROL_MAX { return false; }
+                q.control.push_back((kind, msg));
+            }
+            Priority::Count => {
+                if let Some((old, _)) = q.count.replace((kind, msg)) { self.metrics.dropped(old); }
+            }
+            Priority::Event => {
+                if q.events.len() >= EVENT_MAX {
+                    if let Some((old, _)) = q.events.pop_front() { self.metrics.dropped(old); }
+                }
+                q.events.push_back((kind, msg));
+            }
+        }
+        drop(q);
+        self.notify.notify_one();
+        true
+    }
+
+    /// 人数帧尚未发出（此时应发完整 `sync` 而非 `delta`，因为它会被覆盖）
+    pub fn count_pending(&self) -> bool { self.queues.lock().unwrap().count.is_some() }
+
+    /// 不再接受新消息；写任务发完已入队的控制帧后结束，未发出的人数与事件帧丢弃
+    pub fn close(&self) {
+        let mut q = self.queues.lock().unwrap();
+        q.closed = true;
+        q.count = None;
+        q.events.clear();
+        drop(q);
+        self.notify.notify_one();
+    }
+
+    async fn next(&self) -> Option<(&'static str, Priority, Message)> {
+        loop {
+            {
+                let mut q = self.queues.lock().unwrap();
+                if let Some((k, m)) = q.control.pop_front() { return Some((k, Priority::Control, m)); }
+                if let Some((k, m)) = q.count.take() { return Some((k, Priority::Count, m)); }
+                if let Some((k, m)) = q.events.pop_front() { return Some((k, Priority::Event, m)); }
+                if q.closed { return None; }
+            }
+            self.notify.notified().await;
+        }
+    }
+}
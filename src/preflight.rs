@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use crate::config::{Config, ConfigProblem};
+use crate::sink;
+
+/// 汇总格式问题与选项间的冲突
+pub fn check(cfg: &Config) -> Vec<ConfigProblem> {
+    let mut out = cfg.problems.clone();
+    let mut error = |key, msg: String| out.push(ConfigProblem::error(key, msg));
+    for entry in cfg.allowed_origins.iter().flatten() {
+        if let Some(why) = origin_rule_problem(entry) { error("ALLOWED_ORIGINS", format!("entry {entry:?} {why}")); }
+    }
+    for (entry, _) in &cfg.origin_quotas.rules {
+        if let Some(why) = origin_rule_problem(entry) { error("ORIGIN_QUOTAS", format!("entry {entry:?} {why}")); }
+    }
+    if let (Some(idle), Some(ping)) = (cfg.idle_timeout, cfg.ping_interval) {
+        if idle <= ping {
+            error("IDLE_TIMEOUT", format!("{} must exceed PING_INTERVAL ({}), otherwise quiet clients are dropped before they are pinged", ms(idle), ms(ping)));
+        }
+    }
+    if cfg.admin_addr.is_some_and(|a| a.port() == cfg.port) {
+        error("ADMIN_PORT", format!("must differ from PORT ({})", cfg.port));
+    }
+    if !cfg.public_metrics && cfg.admin_token.is_none() && cfg.api_tokens.is_empty() {
+        error("PUBLIC_METRICS", "is off but neither ADMIN_TOKEN nor API_TOKENS is set, metrics would be unreachable".to_string());
+    }
+    if cfg.history_interval >= cfg.history_retention {
+        error("HISTORY_INTERVAL", format!("{} must be shorter than HISTORY_RETENTION ({})", ms(cfg.history_interval), ms(cfg.history_retention)));
+    }
+    if cfg.resume_secret.is_some() && cfg.resume_ttl.is_zero() {
+        error("RESUME_TTL", "must be positive when RESUME_SECRET is set".to_string());
+    }
+    if let Some(spec) = &cfg.event_sink {
+        if sink::from_config(spec, None).is_none() { error("EVENT_SINK", format!("{spec:?} unsupported, expected file:<path> or http://...")); }
+    }
+    if let Some(url) = cfg.alerts.webhook.as_deref().filter(|u| !u.starts_with("http://")) {
+        error("ALERT_WEBHOOK", format!("{url:?} unsupported, only http:// is supported"));
+    }
+    if let Some(m) = cfg.mirror.as_ref().filter(|m| !m.upstream.starts_with("ws://") && !m.upstream.starts_with("wss://")) {
+        error("MIRROR_UPSTREAM", format!("{:?} must be a ws:// or wss:// URL", m.upstream));
+    }
+
+    let mut warn = |key, msg: String| out.push(ConfigProblem::warning(key, msg));
+    match (cfg.idle_timeout, cfg.ping_interval) {
+        (Some(idle), Some(ping)) if idle > ping && idle <= ping + cfg.ping_jitter => {
+            warn("PING_JITTER", format!("PING_INTERVAL + PING_JITTER reaches IDLE_TIMEOUT ({}), some first pings may arrive too late", ms(idle)));
+        }
+        (Some(_), None) => warn("IDLE_TIMEOUT", "set without PING_INTERVAL, clients that only listen will be dropped".to_string()),
+        _ => {}
+    }
+    if !cfg.ping_jitter.is_zero() && cfg.ping_interval.is_none() {
+        warn("PING_JITTER", "has no effect without PING_INTERVAL".to_string());
+    }
+    if cfg.limit_overflow && cfg.max_connections.is_none() && cfg.origin_quotas.rules.is_empty() && cfg.origin_quotas.default_max.is_none() {
+        warn("LIMIT_OVERFLOW", "has no effect without MAX_CONNECTIONS or origin quotas".to_string());
+    }
+    if cfg.reconnect.ban_after.is_some() && cfg.reconnect.max_per_minute.is_none() {
+        warn("RECONNECT_BAN_AFTER", "has no effect without RECONNECT_MAX_PER_MINUTE".to_string());
+    }
+    if cfg.mirror.as_ref().is_some_and(|m| m.token.is_none()) {
+        warn("MIRROR_TOKEN", "unset, the upstream will ignore reports from this instance".to_string());
+    }
+    if cfg.event_signing_secret.is_some() && cfg.event_sink.is_none() && cfg.alerts.webhook.is_none() {
+        warn("EVENT_SIGNING_SECRET", "set but neither EVENT_SINK nor ALERT_WEBHOOK is configured".to_string());
+    }
+    if cfg.synthetic_presence && cfg.admin_token.is_none() && cfg.api_tokens.is_empty() {
+        warn("SYNTHETIC_PRESENCE", "admin routes are disabled without ADMIN_TOKEN or API_TOKENS".to_string());
+    }
+    if cfg.shed.enabled() && cfg.sync_batch.is_some_and(|b| b > cfg.shed.sync_batch) {
+        warn("SHED_SYNC_BATCH_MS", "is below SYNC_BATCH_MS and will not widen the batch window".to_string());
+    }
+    if cfg.privacy_mode && cfg.event_history > 0 {
+        warn("EVENT_HISTORY", "has no effect with PRIVACY_MODE, only aggregate events are kept and those are not buffered".to_string());
+    }
+    out
+}
+
+/// 规则语法见 `ALLOWED_ORIGINS`：`*`、`http(s)://host[:port]`、`*.suffix` / `.suffix`、`host[:port]`
+fn origin_rule_problem(entry: &str) -> Option<&'static str> {
+    let e = entry.trim().trim_end_matches('/');
+    if e == "*" { return None; }
+    let host_port = match e.split_once("://") {
+        Some(("http" | "https", rest)) => rest,
+        Some(_) => return Some("has an unsupported scheme, expected http:// or https://"),
+        None => e.strip_prefix("*.").or_else(|| e.strip_prefix('.')).unwrap_or(e),
+    };
+    if host_port.contains(['/', '?', '#']) { return Some("must not contain a path"); }
+    if host_port.contains('*') { return Some("only supports a leading *. wildcard"); }
+    let host = match host_port.rsplit_once(':') {
+        Some((h, p)) if !h.contains(':') || h.ends_with(']') => {
+            if p.parse::<u16>().is_err() { return Some("has an invalid port"); }
+            h
+        }
+        _ => host_port,
+    };
+    if host.is_empty() || host.contains(char::is_whitespace) { return Some("has an empty or invalid host"); }
+    None
+}
+
+/// 生效配置表；密钥仅显示是否配置
+pub fn table(cfg: &Config) -> String {
+    let secret = |v: &Option<String>| if v.is_some() { "<redacted>".to_string() } else { "-".to_string() };
+    let opt = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+    let dur = |d: Option<Duration>| opt(d.map(ms));
+    let num = |n: Option<usize>| opt(n.map(|n| n.to_string()));
+    let list = |mut v: Vec<String>| if v.is_empty() { "-".to_string() } else { v.sort(); v.join(",") };
+    let rows: Vec<(&str, String)> = vec![
+        ("NODE_ID", cfg.node_id.clone()),
+        ("PORT", cfg.port.to_string()),
+        ("ADMIN_PORT", opt(cfg.admin_addr.map(|a| a.to_string()))),
+        ("ADMIN_TOKEN", secret(&cfg.admin_token)),
+        ("API_TOKENS", list(cfg.api_tokens.iter().map(|t| t.name.clone()).collect())),
+        ("PUBLIC_METRICS", cfg.public_metrics.to_string()),
+        ("ALLOWED_ORIGINS", cfg.allowed_origins.as_ref().map_or("*".to_string(), |s| list(s.iter().cloned().collect()))),
+        ("ORIGIN_QUOTAS", list(cfg.origin_quotas.rules.iter().map(|(o, n)| format!("{o}={n}")).collect())),
+        ("COUNT_MODE", format!("{:?}", cfg.count_mode).to_ascii_lowercase()),
+        ("SID_GENERATOR", format!("{:?}", cfg.sid_generator)),
+        ("UPDATE_SID", format!("{:?}", cfg.update_sid).to_ascii_lowercase()),
+        ("TRUST_PROXY", cfg.trust_proxy.to_string()),
+        ("PING_INTERVAL", dur(cfg.ping_interval)),
+        ("PING_JITTER", ms(cfg.ping_jitter)),
+        ("IDLE_TIMEOUT", dur(cfg.idle_timeout)),
+        ("LEAVE_GRACE_MS", dur(cfg.leave_grace)),
+        ("MAX_FRAME_BYTES", cfg.max_frame_bytes.to_string()),
+        ("MAX_CONNECTIONS", num(cfg.max_connections)),
+        ("LIMIT_OVERFLOW", cfg.limit_overflow.to_string()),
+        ("IP_MAX_CONNECTIONS", num(cfg.ip_limits.max_connections)),
+        ("IP_ALLOWLIST", list(cfg.ip_allowlist.iter().map(|c| c.to_string()).collect())),
+        ("IP_DENYLIST", list(cfg.ip_denylist.iter().map(|c| c.to_string()).collect())),
+        ("RECONNECT_MAX_PER_MINUTE", opt(cfg.reconnect.max_per_minute.map(|n| n.to_string()))),
+        ("SYNC_BATCH_MS", dur(cfg.sync_batch)),
+        ("DELTA_THRESHOLD", num(cfg.delta_threshold)),
+        ("RESUME_SECRET", secret(&cfg.resume_secret)),
+        ("RESUME_TTL", ms(cfg.resume_ttl)),
+        ("SESSION_SECRET", secret(&cfg.session_secret)),
+        ("EVENT_SINK", opt(cfg.event_sink.clone())),
+        ("EVENT_HISTORY", cfg.event_history.to_string()),
+        ("EVENT_SIGNING_SECRET", secret(&cfg.event_signing_secret)),
+        ("ALERT_WEBHOOK", opt(cfg.alerts.webhook.clone())),
+        ("HISTORY_INTERVAL", ms(cfg.history_interval)),
+        ("HISTORY_RETENTION", ms(cfg.history_retention)),
+        ("CHURN_WINDOW", ms(cfg.churn_window)),
+        ("STATS_TIMEZONE", cfg.stats_timezone.to_string()),
+        ("GEO_HEADER", opt(cfg.geo_header.clone())),
+        ("LOAD_SHEDDING", cfg.shed.enabled().to_string()),
+        ("MIRROR_UPSTREAM", opt(cfg.mirror.as_ref().map(|m| format!("{} (node {})", m.upstream, m.node)))),
+        ("MIRROR_TOKEN", secret(&cfg.mirror.as_ref().and_then(|m| m.token.clone()))),
+        ("SYNTHETIC_PRESENCE", cfg.synthetic_presence.to_string()),
+        ("PRIVACY_MODE", cfg.privacy_mode.to_string()),
+    ];
+    let width = rows.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+    rows.iter().map(|(k, v)| format!("  {k:<width$}  {v}")).collect::<Vec<_>>().join("\n")
+}
+
+/// 输出配置表与问题；存在错误时返回 `false`
+pub fn report(cfg: &Config) -> bool {
+    tracing::info!("effective config:\n{}", table(cfg));
+    let problems = check(cfg);
+    for p in &problems {
+        if p.fatal {
+            tracing::error!("{}: {}", p.key, p.message);
+        } else {
+            tracing::warn!("{}: {}", p.key, p.message);
+        }
+    }
+    let errors = problems.iter().filter(|p| p.fatal).count();
+    if errors > 0 { tracing::error!(errors, "refusing to start, fix the configuration above"); }
+    errors == 0
+}
+
+fn ms(d: Duration) -> String {
+    if d.subsec_millis() == 0 { format!("{}s", d.as_secs()) } else { format!("{}ms", d.as_millis()) }
+}
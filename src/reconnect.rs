@@ -0,0 +1,139 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use serde::Serialize;
+
+use crate::config::ReconnectLimits;
+use crate::supervisor::Supervisor;
+use crate::time::SharedClock;
+
+const WINDOW_MS: u64 = 60_000;
+/// 首次惩罚时长，之后每次违规翻倍
+const BASE_PENALTY_MS: u64 = 5_000;
+const MAX_PENALTY_MS: u64 = 15 * 60_000;
+/// 超过该时长无新违规则清零违规次数
+const STRIKE_RESET_MS: u64 = 10 * 60_000;
+
+#[derive(Debug, Clone, Default)]
+struct Entry {
+    window_start_ms: u64,
+    connects: u32,
+    strikes: u32,
+    last_strike_ms: u64,
+    penalty_until_ms: u64,
+    /// 惩罚期内被拒绝的握手数
+    rejected: u64,
+    last_seen_ms: u64,
+}
+
+/// 握手判定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    /// 拒绝握手，`retry_after_secs` 后再试
+    Penalty { retry_after_secs: u64 },
+    /// 违规次数达到 `RECONNECT_BAN_AFTER`，应对 `key` 施加临时封禁
+    Ban { key: String },
+}
+
+/// 管理接口展示的高频重连来源
+#[derive(Debug, Clone, Serialize)]
+pub struct Offender {
+    /// `ip:<网段>` 或 `session:<会话 ID>`
+    pub key: String,
+    pub connects_in_window: u32,
+    pub strikes: u32,
+    pub penalty_until_ms: Option<u64>,
+    pub rejected: u64,
+    pub last_seen_ms: u64,
+}
+
+/// 按网段 / 会话统计每分钟握手次数，超过上限后指数退避拒绝
+pub struct ReconnectGuard {
+    limits: ReconnectLimits,
+    entries: DashMap<String, Entry>,
+}
+
+impl ReconnectGuard {
+    pub fn new(limits: ReconnectLimits) -> Self {
+        Self { limits, entries: DashMap::new() }
+    }
+
+    /// 登记一次握手；任一键处于惩罚期或本次超限即拒绝
+    pub fn check(&self, keys: &[String], now_ms: u64) -> Verdict {
+        let Some(limit) = self.limits.max_per_minute else { return Verdict::Allow };
+        let mut verdict = Verdict::Allow;
+        for key in keys {
+            let mut e = self.entries.entry(key.clone()).or_default();
+            e.last_seen_ms = now_ms;
+            if now_ms < e.penalty_until_ms {
+                e.rejected += 1;
+                if verdict == Verdict::Allow {
+                    verdict = Verdict::Penalty { retry_after_secs: (e.penalty_until_ms - now_ms).div_ceil(1000) };
+                }
+                continue;
+            }
+            if now_ms.saturating_sub(e.window_start_ms) >= WINDOW_MS {
+                e.window_start_ms = now_ms;
+                e.connects = 0;
+            }
+            e.connects += 1;
+            if e.connects <= limit { continue; }
+
+            if now_ms.saturating_sub(e.last_strike_ms) > STRIKE_RESET_MS { e.strikes = 0; }
+            e.strikes += 1;
+            e.last_strike_ms = now_ms;
+            e.rejected += 1;
+            let penalty = BASE_PENALTY_MS.saturating_mul(1 << (e.strikes - 1).min(16)).min(MAX_PENALTY_MS);
+            e.penalty_until_ms = now_ms + penalty;
+            e.window_start_ms = now_ms;
+            e.connects = 0;
+            tracing::warn!(key = %key, strikes = e.strikes, penalty_ms = penalty, "reconnect loop detected");
+            if self.limits.ban_after.is_some_and(|n| e.strikes >= n) {
+                return Verdict::Ban { key: key.clone() };
+            }
+            if verdict == Verdict::Allow {
+                verdict = Verdict::Penalty { retry_after_secs: penalty.div_ceil(1000) };
+            }
+        }
+        verdict
+    }
+
+    pub fn ban_duration(&self) -> Duration { self.limits.ban_duration }
+
+    /// 有违规记录的来源，按违规次数降序
+    pub fn offenders(&self, now_ms: u64) -> Vec<Offender> {
+        let mut v: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|e| e.strikes > 0)
+            .map(|e| Offender {
+                key: e.key().clone(),
+                connects_in_window: e.connects,
+                strikes: e.strikes,
+                penalty_until_ms: (e.penalty_until_ms > now_ms).then_some(e.penalty_until_ms),
+                rejected: e.rejected,
+                last_seen_ms: e.last_seen_ms,
+            })
+            .collect();
+        v.sort_by(|a, b| b.strikes.cmp(&a.strikes).then_with(|| b.last_seen_ms.cmp(&a.last_seen_ms)));
+        v
+    }
+
+    /// 定期清理长时间无握手且不在惩罚期的记录
+    pub fn spawn_sweeper(self: Arc<Self>, tasks: &Arc<Supervisor>, clock: SharedClock) {
+        if self.limits.max_per_minute.is_none() { return; }
+        tasks.spawn("reconnect_sweeper", move || {
+            let (this, clock) = (self.clone(), clock.clone());
+            async move {
+                let mut tick = tokio::time::interval(Duration::from_millis(WINDOW_MS));
+                loop {
+                    tick.tick().await;
+                    let now = clock.now_ms();
+                    this.entries.retain(|_, e| now < e.penalty_until_ms || now.saturating_sub(e.last_seen_ms) < STRIKE_RESET_MS);
+                }
+            }
+        });
+    }
+}
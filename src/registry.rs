@@ -0,0 +1,249 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use activenow_protocol::CountryCount;
+use dashmap::DashMap;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::events::{CloseReason, Geo};
+
+/// 单个连接的运行时信息（仅网关内存，不进入 MetaStore）
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnInfo {
+    pub sid: String,
+    pub session_id: String,
+    pub connected_at_ms: u64,
+    pub remote_ip: Option<String>,
+    /// `remote_ip` 按 `IP_V4_PREFIX` / `IP_V6_PREFIX` 聚合后的网段
+    pub subnet: String,
+    pub origin: Option<String>,
+    pub last_seen_ms: u64,
+    pub msgs_in: u64,
+    pub msgs_out: u64,
+    /// 超长、二进制或无法解析的上行帧数
+    pub malformed: u64,
+    /// 观察者连接（`observe=1`，不计入在线）
+    pub observer: bool,
+    /// `AuthProvider` 返回的用户标识
+    pub user_id: Option<String>,
+    /// 最近一次 Ping/Pong 往返时延（需开启 `PING_INTERVAL`）
+    pub rtt_ms: Option<u64>,
+    /// 溢出连接：超出连接上限后接入，计入在线但不接收 `sync` 推送
+    pub overflow: bool,
+    /// 代理头 `GEO_HEADER` 给出的国家代码
+    pub country: Option<String>,
+    /// 向连接任务下发主动断开指令
+    #[serde(skip)]
+    pub control: Option<mpsc::UnboundedSender<CloseReason>>,
+}
+
+/// 单个来源的累计统计
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OriginStats {
+    pub origin: String,
+    /// 当前连接数
+    pub live: usize,
+    /// 当前溢出连接数（已含在 `live` 中）
+    pub overflow: usize,
+    /// 累计连接数
+    pub total: u64,
+    pub msgs_in: u64,
+    pub msgs_out: u64,
+    /// 最近 `CHURN_WINDOW` 内新接入的连接数（不含观察者）
+    pub joins: u64,
+    /// 最近 `CHURN_WINDOW` 内断开的连接数（不含观察者）
+    pub leaves: u64,
+    #[serde(skip)]
+    churn: VecDeque<ChurnBucket>,
+    /// 最近一次接入 / 断开时刻（毫秒），用于清理无连接的来源
+    #[serde(skip)]
+    last_ms: u64,
+}
+
+/// 进出计数的时间桶；窗口按 `CHURN_BUCKETS` 等分，滑动精度为一个桶宽
+#[derive(Debug, Clone, Copy)]
+struct ChurnBucket { start_ms: u64, joins: u64, leaves: u64 }
+
+const CHURN_BUCKETS: u64 = 12;
+
+impl OriginStats {
+    fn record(&mut self, now_ms: u64, width_ms: u64, join: bool) {
+        let start_ms = now_ms - now_ms % width_ms;
+        if self.churn.back().is_none_or(|b| b.start_ms != start_ms) {
+            self.churn.push_back(ChurnBucket { start_ms, joins: 0, leaves: 0 });
+            if self.churn.len() as u64 > CHURN_BUCKETS + 1 { self.churn.pop_front(); }
+        }
+        let b = self.churn.back_mut().unwrap();
+        if join { b.joins += 1 } else { b.leaves += 1 }
+    }
+
+    /// 按 `now_ms` 汇总窗口内的进出数
+    fn settle(&mut self, now_ms: u64, window_ms: u64) {
+        let since = now_ms.saturating_sub(window_ms);
+        let live = self.churn.iter().filter(|b| b.start_ms >= since);
+        (self.joins, self.leaves) = live.fold((0, 0), |(j, l), b| (j + b.joins, l + b.leaves));
+    }
+}
+
+/// 在线连接的往返时延分布
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencyStats {
+    /// 已测得 RTT 的连接数
+    pub samples: usize,
+    pub p50_ms: Option<u64>,
+    pub p95_ms: Option<u64>,
+}
+
+/// 在线连接登记表，供管理接口查询
+#[derive(Default)]
+pub struct ConnRegistry {
+    inner: DashMap<String, ConnInfo>,
+    /// 以规范化 Origin（缺失为 `(none)`）为键
+    origins: DashMap<String, OriginStats>,
+    /// 网段 -> 当前连接数
+    subnets: DashMap<String, usize>,
+    churn_window: Duration,
+    last_sweep_ms: AtomicU64,
+}
+
+fn origin_key(origin: Option<&str>) -> &str { origin.unwrap_or("(none)") }
+
+impl ConnRegistry {
+    /// `churn_window` 为进出统计的滑动窗口（`CHURN_WINDOW`）
+    pub fn new(churn_window: Duration) -> Self {
+        Self { churn_window: churn_window.max(Duration::from_secs(CHURN_BUCKETS)), ..Default::default() }
+    }
+
+    pub fn churn_window(&self) -> Duration { self.churn_window }
+
+    fn bucket_ms(&self) -> u64 { self.churn_window.as_millis() as u64 / CHURN_BUCKETS }
+
+    pub fn register(&self, info: ConnInfo) {
+        let key = origin_key(info.origin.as_deref()).to_string();
+        let mut o = self.origins.entry(key.clone()).or_insert_with(|| OriginStats { origin: key, ..Default::default() });
+        o.live += 1;
+        o.total += 1;
+        o.last_ms = o.last_ms.max(info.connected_at_ms);
+        if info.overflow { o.overflow += 1; }
+        if !info.observer { o.record(info.connected_at_ms, self.bucket_ms(), true); }
+        drop(o);
+        *self.subnets.entry(info.subnet.clone()).or_default() += 1;
+        let now_ms = info.connected_at_ms;
+        self.inner.insert(info.sid.clone(), info);
+        self.sweep_origins(now_ms);
+    }
+
+    pub fn unregister(&self, sid: &str, now_ms: u64) -> Option<ConnInfo> {
+        let (_, c) = self.inner.remove(sid)?;
+        if let Some(mut o) = self.origins.get_mut(origin_key(c.origin.as_deref())) {
+            o.live = o.live.saturating_sub(1);
+            o.last_ms = o.last_ms.max(now_ms);
+            if c.overflow { o.overflow = o.overflow.saturating_sub(1); }
+            if !c.observer { o.record(now_ms, self.bucket_ms(), false); }
+        }
+        self.subnets.remove_if_mut(&c.subnet, |_, n| { *n = n.saturating_sub(1); *n == 0 });
+        self.sweep_origins(now_ms);
+        Some(c)
+    }
+
+    /// Origin 由客户端提供，无连接且超过一个进出窗口未活动的来源移除（累计值随之丢弃）；每个桶宽至多清理一次
+    fn sweep_origins(&self, now_ms: u64) {
+        let last = self.last_sweep_ms.load(Ordering::Relaxed);
+        if now_ms < last + self.bucket_ms() { return; }
+        if self.last_sweep_ms.compare_exchange(last, now_ms, Ordering::Relaxed, Ordering::Relaxed).is_err() { return; }
+        let window_ms = self.churn_window.as_millis() as u64;
+        self.origins.retain(|_, o| o.live > 0 || now_ms.saturating_sub(o.last_ms) < window_ms);
+    }
+
+    fn bump_origin(&self, sid: &str, f: impl FnOnce(&mut OriginStats)) {
+        let origin = match self.inner.get(sid) { Some(c) => c.origin.clone(), None => return };
+        if let Some(mut o) = self.origins.get_mut(origin_key(origin.as_deref())) { f(&mut o); }
+    }
+
+    pub fn subnet_live(&self, subnet: &str) -> usize {
+        self.subnets.get(subnet).map(|n| *n).unwrap_or(0)
+    }
+
+    /// 来源当前的订阅连接数（不含溢出连接）
+    pub fn origin_live(&self, origin: Option<&str>) -> usize {
+        self.origins.get(origin_key(origin)).map(|o| o.live - o.overflow).unwrap_or(0)
+    }
+
+    /// 当前溢出连接总数
+    pub fn overflow_len(&self) -> usize {
+        self.origins.iter().map(|o| o.overflow).sum()
+    }
+
+    /// 各来源统计，按当前连接数降序
+    pub fn origin_stats(&self, now_ms: u64) -> Vec<OriginStats> {
+        let window_ms = self.churn_window.as_millis() as u64;
+        let mut v: Vec<_> = self.origins.iter_mut().map(|mut o| { o.settle(now_ms, window_ms); o.clone() }).collect();
+        v.sort_by(|a, b| b.live.cmp(&a.live).then_with(|| b.total.cmp(&a.total)).then_with(|| a.origin.cmp(&b.origin)));
+        v
+    }
+
+    pub fn update(&self, sid: &str, f: impl FnOnce(&mut ConnInfo)) {
+        if let Some(mut ent) = self.inner.get_mut(sid) { f(&mut ent); }
+    }
+
+    /// 收到客户端帧：刷新活跃时间并计数
+    pub fn on_inbound(&self, sid: &str, now_ms: u64) {
+        self.update(sid, |c| { c.msgs_in += 1; c.last_seen_ms = now_ms; });
+        self.bump_origin(sid, |o| o.msgs_in += 1);
+    }
+
+    pub fn on_outbound(&self, sid: &str) {
+        self.update(sid, |c| c.msgs_out += 1);
+        self.bump_origin(sid, |o| o.msgs_out += 1);
+    }
+
+    /// 通知指定连接断开；连接不存在时返回 false
+    pub fn close(&self, sid: &str, reason: CloseReason) -> bool {
+        self.inner.get(sid).and_then(|c| c.control.as_ref().map(|tx| tx.send(reason).is_ok())).unwrap_or(false)
+    }
+
+    pub fn close_all(&self, reason: CloseReason) {
+        for c in self.inner.iter() {
+            if let Some(tx) = &c.control { let _ = tx.send(reason); }
+        }
+    }
+
+    pub fn len(&self) -> usize { self.inner.len() }
+
+    pub fn is_empty(&self) -> bool { self.inner.is_empty() }
+
+    pub fn contains(&self, sid: &str) -> bool { self.inner.contains_key(sid) }
+
+    pub fn get(&self, sid: &str) -> Option<ConnInfo> { self.inner.get(sid).map(|c| c.clone()) }
+
+    pub fn latency(&self) -> LatencyStats {
+        let mut v: Vec<u64> = self.inner.iter().filter_map(|c| c.rtt_ms).collect();
+        v.sort_unstable();
+        let pick = |q: usize| (!v.is_empty()).then(|| v[((v.len() - 1) * q).div_ceil(100)]);
+        LatencyStats { samples: v.len(), p50_ms: pick(50), p95_ms: pick(95) }
+    }
+
+    /// 按国家汇总在线连接（不含观察者），按在线数降序
+    pub fn country_breakdown(&self, now_ms: u64) -> Geo {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let mut unknown = 0;
+        for c in self.inner.iter().filter(|c| !c.observer) {
+            match &c.country {
+                Some(cc) => *counts.entry(cc.clone()).or_default() += 1,
+                None => unknown += 1,
+            }
+        }
+        let mut countries: Vec<_> = counts.into_iter().map(|(country, online)| CountryCount { country, online }).collect();
+        countries.sort_by(|a, b| b.online.cmp(&a.online).then_with(|| a.country.cmp(&b.country)));
+        Geo { countries, unknown, ts: now_ms }
+    }
+
+    /// 按连接时间升序返回满足条件的连接
+    pub fn list(&self, filter: impl Fn(&ConnInfo) -> bool) -> Vec<ConnInfo> {
+        let mut v: Vec<_> = self.inner.iter().filter(|c| filter(c)).map(|c| c.clone()).collect();
+        v.sort_by(|a, b| a.connected_at_ms.cmp(&b.connected_at_ms).then_with(|| a.sid.cmp(&b.sid)));
+        v
+    }
+}
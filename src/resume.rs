@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+use crate::sign;
+
+/// 断线重连令牌：在有效期内凭令牌找回原 `sid` 与会话标识
+pub struct ResumeKeys {
+    secret: Vec<u8>,
+    ttl: Duration,
+}
+
+/// 令牌中携带的身份
+pub struct Resumed {
+    pub sid: String,
+    pub session_id: String,
+}
+
+impl ResumeKeys {
+    /// 未配置密钥时使用进程内随机密钥（令牌仅对本实例有效）
+    pub fn new(secret: Option<&str>, ttl: Duration) -> Self {
+        let secret = match secret {
+            Some(s) => s.as_bytes().to_vec(),
+            None => (0..32).map(|_| rand::random::<u8>()).collect(),
+        };
+        Self { secret, ttl }
+    }
+
+    pub fn issue(&self, sid: &str, session_id: &str, now_ms: u64) -> String {
+        let exp = now_ms + self.ttl.as_millis() as u64;
+        sign::seal(&self.secret, &format!("{exp}\n{sid}\n{session_id}"))
+    }
+
+    pub fn verify(&self, token: &str, now_ms: u64) -> Option<Resumed> {
+        let raw = sign::open(&self.secret, token)?;
+        let mut it = raw.splitn(3, '\n');
+        let exp: u64 = it.next()?.parse().ok()?;
+        if exp < now_ms { return None; }
+        Some(Resumed { sid: it.next()?.to_string(), session_id: it.next()?.to_string() })
+    }
+}
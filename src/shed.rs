@@ -0,0 +1,123 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use crate::supervisor::Supervisor;
+
+const CHECK_EVERY: Duration = Duration::from_secs(1);
+/// 各项指标均低于阈值的该比例才视为压力缓解
+const RECOVER_RATIO: f64 = 0.8;
+/// 连续缓解的采样次数达到该值后退出降载
+const RECOVER_AFTER: u32 = 5;
+
+/// 降载阈值；均未配置时不启动监测
+#[derive(Debug, Clone, Default)]
+pub struct ShedConfig {
+    /// 进程常驻内存上限（`SHED_RSS_MB`）
+    pub rss_mb: Option<u64>,
+    /// 调度延迟上限（`SHED_LAG_MS`）：定时器实际唤醒晚于预期的时长，反映 CPU 饱和
+    pub lag: Option<Duration>,
+    /// tokio 全局队列积压任务数上限（`SHED_QUEUE_DEPTH`）
+    pub queue_depth: Option<usize>,
+    /// 降载期间 `sync` 合并窗口的下限（`SHED_SYNC_BATCH_MS`）
+    pub sync_batch: Duration,
+}
+
+impl ShedConfig {
+    pub fn enabled(&self) -> bool { self.rss_mb.is_some() || self.lag.is_some() || self.queue_depth.is_some() }
+}
+
+/// 最近一次采样
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Pressure {
+    /// 仅 Linux 可读
+    pub rss_mb: Option<u64>,
+    pub lag_ms: u64,
+    pub queue_depth: usize,
+    pub alive_tasks: usize,
+}
+
+/// 内存 / CPU 压力下的自动降载：拒绝新连接、放宽 `sync` 合并窗口、暂停 `geo` 推送
+pub struct LoadShedder {
+    config: ShedConfig,
+    active_tx: watch::Sender<bool>,
+    last: Mutex<Pressure>,
+}
+
+impl LoadShedder {
+    pub fn new(config: ShedConfig) -> Arc<Self> {
+        Arc::new(Self { config, active_tx: watch::channel(false).0, last: Default::default() })
+    }
+
+    pub fn enabled(&self) -> bool { self.config.enabled() }
+
+    pub fn active(&self) -> bool { *self.active_tx.borrow() }
+
+    pub fn subscribe(&self) -> watch::Receiver<bool> { self.active_tx.subscribe() }
+
+    pub fn pressure(&self) -> Pressure { self.last.lock().unwrap().clone() }
+
+    pub fn sync_batch(&self) -> Duration { self.config.sync_batch }
+
+    pub fn spawn_monitor(self: Arc<Self>, tasks: &Arc<Supervisor>) {
+        if !self.enabled() { return; }
+        tasks.spawn("load_shedder", move || {
+            let this = self.clone();
+            async move {
+                let mut calm = 0u32;
+                loop {
+                    let started = Instant::now();
+                    tokio::time::sleep(CHECK_EVERY).await;
+                    let p = sample(started.elapsed().saturating_sub(CHECK_EVERY));
+                    let over = this.ratio(&p);
+                    *this.last.lock().unwrap() = p.clone();
+                    let active = this.active();
+                    if over >= 1.0 {
+                        calm = 0;
+                        if !active {
+                            tracing::warn!(rss_mb = ?p.rss_mb, lag_ms = p.lag_ms, queue_depth = p.queue_depth, "load shedding on");
+                            this.active_tx.send_replace(true);
+                        }
+                    } else if active && over < RECOVER_RATIO {
+                        calm += 1;
+                        if calm >= RECOVER_AFTER {
+                            calm = 0;
+                            tracing::info!(rss_mb = ?p.rss_mb, lag_ms = p.lag_ms, queue_depth = p.queue_depth, "load shedding off");
+                            this.active_tx.send_replace(false);
+                        }
+                    } else {
+                        calm = 0;
+                    }
+                }
+            }
+        });
+    }
+
+    /// 各项指标相对阈值的最大比例
+    fn ratio(&self, p: &Pressure) -> f64 {
+        let c = &self.config;
+        [
+            c.rss_mb.zip(p.rss_mb).map(|(max, v)| v as f64 / max.max(1) as f64),
+            c.lag.map(|max| p.lag_ms as f64 / (max.as_millis().max(1) as f64)),
+            c.queue_depth.map(|max| p.queue_depth as f64 / max.max(1) as f64),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(0.0, f64::max)
+    }
+}
+
+fn sample(lag: Duration) -> Pressure {
+    let rt = tokio::runtime::Handle::current().metrics();
+    Pressure { rss_mb: rss_mb(), lag_ms: lag.as_millis() as u64, queue_depth: rt.global_queue_depth(), alive_tasks: rt.num_alive_tasks() }
+}
+
+/// `/proc/self/statm` 第二列为常驻页数（按 4 KiB 页计）
+fn rss_mb() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096 / (1024 * 1024))
+}
@@ -0,0 +1,58 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha1::{Digest, Sha1};
+
+/// HMAC-SHA1（RFC 2104）
+pub fn hmac_sha1(key: &[u8], msg: &[u8]) -> [u8; 20] {
+    let mut k = [0u8; 64];
+    if key.len() > 64 {
+        k[..20].copy_from_slice(&Sha1::digest(key));
+    } else {
+        k[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha1::new();
+    inner.update(k.map(|b| b ^ 0x36));
+    inner.update(msg);
+    let mut outer = Sha1::new();
+    outer.update(k.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+/// 定长比较，避免时序泄露；长度不同直接返回 `false`
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// `base64url(payload).base64url(hmac)`
+pub fn seal(key: &[u8], payload: &str) -> String {
+    let mac = hmac_sha1(key, payload.as_bytes());
+    format!("{}.{}", URL_SAFE_NO_PAD.encode(payload), URL_SAFE_NO_PAD.encode(mac))
+}
+
+/// 校验 `seal` 生成的令牌并返回原文
+pub fn open(key: &[u8], token: &str) -> Option<String> {
+    let (p, m) = token.split_once('.')?;
+    let payload = URL_SAFE_NO_PAD.decode(p).ok()?;
+    let mac = URL_SAFE_NO_PAD.decode(m).ok()?;
+    let expected = hmac_sha1(key, &payload);
+    if !ct_eq(&mac, &expected) { return None; }
+    String::from_utf8(payload).ok()
+}
+
+/// 事件签名：`sha1=<base64url(hmac(body))>`，用于 webhook 的 `X-Signature` 头与 JSON Lines 的 `sig` 字段
+pub fn event_signature(key: &[u8], body: &str) -> String {
+    format!("sha1={}", URL_SAFE_NO_PAD.encode(hmac_sha1(key, body.as_bytes())))
+}
+
+/// 签名会话 ID：`<id>.<base64url(hmac(id))>`
+pub fn sign_id(key: &[u8], id: &str) -> String {
+    format!("{id}.{}", URL_SAFE_NO_PAD.encode(hmac_sha1(key, id.as_bytes())))
+}
+
+/// 校验 `sign_id` 生成的会话 ID，返回 `<id>` 部分
+pub fn verify_id<'a>(key: &[u8], signed: &'a str) -> Option<&'a str> {
+    let (id, m) = signed.rsplit_once('.')?;
+    let mac = URL_SAFE_NO_PAD.decode(m).ok()?;
+    let expected = hmac_sha1(key, id.as_bytes());
+    (!id.is_empty() && ct_eq(&mac, &expected)).then_some(id)
+}
@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, watch};
+
+use crate::gateway::OnlineStats;
+use crate::registry::ConnRegistry;
+use crate::sign;
+use crate::supervisor::Supervisor;
+use crate::time::SharedClock;
+use crate::webhook;
+
+pub use activenow_protocol::{LeaveReason, SinkEvent};
+
+/// 事件下游；`publish` 失败时由管道按退避重试
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    async fn publish(&self, batch: &[SinkEvent]) -> Result<(), String>;
+}
+
+/// 追加写入 JSON Lines 文件；配置签名密钥时每行末尾追加 `"sig"`，签名覆盖追加前的整行原文
+pub struct FileSink { pub path: String, pub secret: Option<String> }
+
+#[async_trait]
+impl EventSink for FileSink {
+    async fn publish(&self, batch: &[SinkEvent]) -> Result<(), String> {
+        let mut buf = String::new();
+        for ev in batch {
+            let line = serde_json::to_string(ev).map_err(|e| e.to_string())?;
+            match &self.secret {
+                Some(key) => {
+                    let sig = sign::event_signature(key.as_bytes(), &line);
+                    buf.push_str(&line[..line.len() - 1]);
+                    buf.push_str(&format!(",\"sig\":\"{sig}\"}}"));
+                }
+                None => buf.push_str(&line),
+            }
+            buf.push('\n');
+        }
+        let mut f = tokio::fs::OpenOptions::new().create(true).append(true).open(&self.path).await.map_err(|e| e.to_string())?;
+        f.write_all(buf.as_bytes()).await.map_err(|e| e.to_string())
+    }
+}
+
+/// 以 JSON 数组 POST 到 `http://` 地址；配置签名密钥时带 `X-Signature` 头
+pub struct WebhookSink { pub url: String, pub secret: Option<String> }
+
+#[async_trait]
+impl EventSink for WebhookSink {
+    async fn publish(&self, batch: &[SinkEvent]) -> Result<(), String> {
+        let body = serde_json::to_string(batch).map_err(|e| e.to_string())?;
+        webhook::post_json(&self.url, &body, &webhook::signature_headers(self.secret.as_deref(), &body)).await.map(|_| ())
+    }
+}
+
+/// 按 `EVENT_SINK` 构造：`file:<path>` 或 `http://...`；`secret` 为 `EVENT_SIGNING_SECRET`
+pub fn from_config(spec: &str, secret: Option<&str>) -> Option<Arc<dyn EventSink>> {
+    let secret = secret.map(str::to_string);
+    if let Some(path) = spec.strip_prefix("file:") {
+        return Some(Arc::new(FileSink { path: path.to_string(), secret }));
+    }
+    if spec.starts_with("http://") {
+        return Some(Arc::new(WebhookSink { url: spec.to_string(), secret }));
+    }
+    None
+}
+
+const BATCH_MAX: usize = 256;
+const FLUSH_EVERY: Duration = Duration::from_secs(1);
+const RETRIES: u32 = 3;
+
+/// 不含 sid / 会话 / 用户等身份字段的事件
+fn is_aggregate(ev: &SinkEvent) -> bool {
+    matches!(ev, SinkEvent::OnlineChanged { .. } | SinkEvent::OriginChurn { .. })
+}
+
+/// 最近业务事件的内存环（不含 `online_changed` / `origin_churn`），供后加载的看板回放
+pub struct RecentEvents {
+    cap: usize,
+    buf: Mutex<VecDeque<SinkEvent>>,
+}
+
+impl RecentEvents {
+    pub fn new(cap: usize) -> Self {
+        Self { cap, buf: Mutex::new(VecDeque::with_capacity(cap.min(1024))) }
+    }
+
+    fn push(&self, ev: &SinkEvent) {
+        if is_aggregate(ev) { return; }
+        let mut buf = self.buf.lock().unwrap();
+        if buf.len() >= self.cap { buf.pop_front(); }
+        buf.push_back(ev.clone());
+    }
+
+    /// 最近 `limit` 条，按时间先后排列
+    pub fn latest(&self, limit: usize) -> Vec<SinkEvent> {
+        let buf = self.buf.lock().unwrap();
+        buf.iter().skip(buf.len().saturating_sub(limit)).cloned().collect()
+    }
+}
+
+/// 事件缓冲管道：队列满时丢弃新事件，后台按批次（满 256 条或每秒）写入下游；可同时保留最近事件
+#[derive(Clone)]
+pub struct EventPipeline {
+    tx: Option<mpsc::Sender<SinkEvent>>,
+    pub recent: Option<Arc<RecentEvents>>,
+    /// `PRIVACY_MODE`：只放行聚合事件
+    aggregate_only: bool,
+}
+
+impl EventPipeline {
+    /// 下游与事件历史均未配置时返回 `None`
+    pub fn spawn(tasks: &Arc<Supervisor>, sink: Option<Arc<dyn EventSink>>, capacity: usize, history: usize, aggregate_only: bool) -> Option<Self> {
+        if sink.is_none() && history == 0 { return None; }
+        let tx = sink.map(|sink| {
+            let (tx, rx) = mpsc::channel::<SinkEvent>(capacity.max(1));
+            // 接收端放在锁内，任务崩溃重启后接着消费同一队列（未写出的批次丢失）
+            let rx = Arc::new(tokio::sync::Mutex::new(rx));
+            tasks.spawn("event_sink", move || {
+                let (sink, rx) = (sink.clone(), rx.clone());
+                async move {
+                    let mut rx = rx.lock().await;
+                    let mut batch = Vec::with_capacity(BATCH_MAX);
+                    let mut tick = tokio::time::interval(FLUSH_EVERY);
+                    loop {
+                        tokio::select! {
+                            ev = rx.recv() => match ev {
+                                Some(ev) => {
+                                    batch.push(ev);
+                                    if batch.len() >= BATCH_MAX { flush(&*sink, &mut batch).await; }
+                                }
+                                None => { flush(&*sink, &mut batch).await; break; }
+                            },
+                            _ = tick.tick() => flush(&*sink, &mut batch).await,
+                        }
+                    }
+                }
+            });
+            tx
+        });
+        Some(Self { tx, recent: (history > 0).then(|| Arc::new(RecentEvents::new(history))), aggregate_only })
+    }
+
+    pub fn emit(&self, ev: SinkEvent) {
+        if self.aggregate_only && !is_aggregate(&ev) { return; }
+        if let Some(recent) = &self.recent { recent.push(&ev); }
+        let Some(tx) = &self.tx else { return };
+        if tx.try_send(ev).is_err() {
+            tracing::warn!("event sink queue full, dropping event");
+        }
+    }
+
+    /// 将在线人数变化转为 `online_changed` 事件
+    pub fn follow_online(&self, tasks: &Arc<Supervisor>, rx: watch::Receiver<OnlineStats>, clock: SharedClock) {
+        let this = self.clone();
+        tasks.spawn("sink_online", move || {
+            let (this, mut rx, clock) = (this.clone(), rx.clone(), clock.clone());
+            async move {
+                while rx.changed().await.is_ok() {
+                    let s = *rx.borrow_and_update();
+                    this.emit(SinkEvent::OnlineChanged { count: s.count, connections: s.connections, sessions: s.sessions, ts: clock.now_ms() });
+                }
+            }
+        });
+    }
+
+    /// 每个进出统计窗口为有进出的来源各发一条 `origin_churn`
+    pub fn follow_churn(&self, tasks: &Arc<Supervisor>, registry: Arc<ConnRegistry>, clock: SharedClock) {
+        let this = self.clone();
+        tasks.spawn("sink_churn", move || {
+            let (this, registry, clock) = (this.clone(), registry.clone(), clock.clone());
+            async move {
+                let window = registry.churn_window();
+                let mut tick = tokio::time::interval_at(tokio::time::Instant::now() + window, window);
+                loop {
+                    tick.tick().await;
+                    let ts = clock.now_ms();
+                    for o in registry.origin_stats(ts).into_iter().filter(|o| o.joins + o.leaves > 0) {
+                        this.emit(SinkEvent::OriginChurn { origin: o.origin, joins: o.joins, leaves: o.leaves, live: o.live, window_ms: window.as_millis() as u64, ts });
+                    }
+                }
+            }
+        });
+    }
+}
+
+async fn flush(sink: &dyn EventSink, batch: &mut Vec<SinkEvent>) {
+    if batch.is_empty() { return; }
+    for attempt in 0..RETRIES {
+        match sink.publish(batch).await {
+            Ok(()) => { batch.clear(); return; }
+            Err(e) => {
+                tracing::warn!(error = %e, attempt, size = batch.len(), "event sink publish failed");
+                tokio::time::sleep(Duration::from_millis(200 << attempt)).await;
+            }
+        }
+    }
+    tracing::error!(size = batch.len(), "event sink giving up on batch");
+    batch.clear();
+}
@@ -0,0 +1,90 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use futures_util::FutureExt;
+use serde::Serialize;
+
+use crate::time::SharedClock;
+
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// 运行超过该时长后再崩溃视为偶发，退避从头计算
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+/// 后台任务状态（`/readyz`）
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskHealth {
+    pub name: &'static str,
+    /// 正在运行；崩溃后等待重启期间为 false
+    pub running: bool,
+    pub restarts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_panic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_panic_ms: Option<u64>,
+}
+
+/// 后台任务监管：记录 panic，按退避重启，正常结束的任务不再重启
+pub struct Supervisor {
+    tasks: DashMap<&'static str, TaskHealth>,
+    clock: SharedClock,
+}
+
+impl Supervisor {
+    pub fn new(clock: SharedClock) -> Arc<Self> {
+        Arc::new(Self { tasks: DashMap::new(), clock })
+    }
+
+    /// `make` 在每次（重）启动时构造任务，需自行克隆所需状态
+    pub fn spawn<F, Fut>(self: &Arc<Self>, name: &'static str, make: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.insert(name, TaskHealth { name, running: true, restarts: 0, last_panic: None, last_panic_ms: None });
+        let this = self.clone();
+        tokio::spawn(async move {
+            let mut backoff = BASE_BACKOFF;
+            loop {
+                let started = tokio::time::Instant::now();
+                let Err(panic) = std::panic::AssertUnwindSafe(make()).catch_unwind().await else {
+                    this.tasks.remove(name);
+                    return;
+                };
+                let msg = panic_message(&*panic);
+                if started.elapsed() >= STABLE_AFTER { backoff = BASE_BACKOFF; }
+                tracing::error!(task = name, panic = %msg, retry_in_ms = backoff.as_millis() as u64, "background task panicked");
+                if let Some(mut t) = this.tasks.get_mut(name) {
+                    t.running = false;
+                    t.last_panic = Some(msg);
+                    t.last_panic_ms = Some(this.clock.now_ms());
+                }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                if let Some(mut t) = this.tasks.get_mut(name) {
+                    t.running = true;
+                    t.restarts += 1;
+                }
+            }
+        });
+    }
+
+    /// 按名称排序
+    pub fn health(&self) -> Vec<TaskHealth> {
+        let mut v: Vec<_> = self.tasks.iter().map(|t| t.clone()).collect();
+        v.sort_by_key(|t| t.name);
+        v
+    }
+
+    pub fn all_running(&self) -> bool { self.tasks.iter().all(|t| t.running) }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
@@ -0,0 +1,145 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use crate::gateway::{self, AppState};
+
+const MAX_MEMBERS: usize = 100_000;
+const MAX_LIFETIME_SECS: u64 = 30 * 86_400;
+const MAX_CHURN_PER_MIN: f64 = 60_000.0;
+const TICK: Duration = Duration::from_secs(1);
+
+/// 注入参数
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SyntheticSpec {
+    /// 目标人数
+    pub count: usize,
+    /// 每分钟随机进出次数，人数在目标附近波动
+    #[serde(default)]
+    pub churn_per_min: f64,
+    /// 平均停留时长（实际在 0.5～1.5 倍间随机），到期离开并由新成员补位
+    pub lifetime_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyntheticStatus {
+    pub active: usize,
+    pub spec: Option<SyntheticSpec>,
+}
+
+/// 测试 / 预发用的虚拟在线成员：只写入 MetaStore 计入人数，不建立连接、不导出事件
+#[derive(Default)]
+pub struct SyntheticLoad {
+    /// sid -> 离开时刻（毫秒）
+    members: DashMap<String, u64>,
+    spec: Mutex<Option<SyntheticSpec>>,
+    /// 串行化 start / stop 与周期进出，避免停止后仍有成员补位
+    ops: tokio::sync::Mutex<()>,
+    seq: AtomicU64,
+}
+
+impl SyntheticLoad {
+    pub fn status(&self) -> SyntheticStatus {
+        SyntheticStatus { active: self.members.len(), spec: self.spec.lock().unwrap().clone() }
+    }
+
+    async fn join(&self, state: &AppState, spec: &SyntheticSpec, now_ms: u64) {
+        let sid = format!("synthetic-{}", self.seq.fetch_add(1, Ordering::Relaxed));
+        let leave_at = spec
+            .lifetime_secs
+            .map(|s| now_ms.saturating_add((s as f64 * 1000.0 * (0.5 + rand::random::<f64>())) as u64))
+            .unwrap_or(u64::MAX);
+        state.meta.upsert_identity(&sid, sid.clone(), now_ms).await;
+        self.members.insert(sid, leave_at);
+    }
+
+    async fn leave(&self, state: &AppState, sid: &str) {
+        if self.members.remove(sid).is_some() { state.meta.clear(sid, state.clock.now_ms()).await; }
+    }
+}
+
+/// 周期进出任务；未注入或参数不需要进出时空转
+pub fn spawn_churn(state: &AppState) {
+    let st = state.clone();
+    state.tasks.spawn("synthetic_churn", move || {
+        let st = st.clone();
+        async move {
+            let mut tick = tokio::time::interval(TICK);
+            tick.tick().await;
+            loop {
+                tick.tick().await;
+                let _ops = st.synthetic.ops.lock().await;
+                let Some(spec) = st.synthetic.spec.lock().unwrap().clone() else { continue };
+                if spec.churn_per_min <= 0.0 && spec.lifetime_secs.is_none() { continue; }
+                if churn(&st, &spec).await { gateway::publish_online(&st).await; }
+            }
+        }
+    });
+}
+
+/// 替换当前注入并按参数持续进出；返回当前虚拟人数
+pub async fn start(state: &AppState, mut spec: SyntheticSpec) -> usize {
+    let load = state.synthetic.clone();
+    let _ops = load.ops.lock().await;
+    remove_all(state).await;
+    spec.count = spec.count.min(MAX_MEMBERS);
+    spec.lifetime_secs = spec.lifetime_secs.map(|s| s.min(MAX_LIFETIME_SECS));
+    spec.churn_per_min = spec.churn_per_min.clamp(0.0, MAX_CHURN_PER_MIN);
+    let now = state.clock.now_ms();
+    for _ in 0..spec.count { load.join(state, &spec, now).await; }
+    *load.spec.lock().unwrap() = Some(spec);
+    gateway::publish_online(state).await;
+    tracing::info!(count = load.members.len(), "synthetic presence started");
+    load.members.len()
+}
+
+/// 移除全部虚拟成员
+pub async fn stop(state: &AppState) -> usize {
+    let _ops = state.synthetic.ops.lock().await;
+    remove_all(state).await
+}
+
+async fn remove_all(state: &AppState) -> usize {
+    let load = &state.synthetic;
+    *load.spec.lock().unwrap() = None;
+    let sids: Vec<String> = load.members.iter().map(|e| e.key().clone()).collect();
+    for sid in &sids { load.leave(state, sid).await; }
+    if !sids.is_empty() {
+        gateway::publish_online(state).await;
+        tracing::info!(removed = sids.len(), "synthetic presence stopped");
+    }
+    sids.len()
+}
+
+/// 一个周期：到期成员离开并补位，另按 `churn_per_min` 随机进出（离开概率随人数偏离目标而变化）；有变化时返回 true
+async fn churn(state: &AppState, spec: &SyntheticSpec) -> bool {
+    let load = &state.synthetic;
+    let now = state.clock.now_ms();
+    let expired: Vec<String> = load.members.iter().filter(|e| *e.value() <= now).map(|e| e.key().clone()).collect();
+    let rate = spec.churn_per_min / 60.0 * TICK.as_secs_f64();
+    let fill = load.members.len() as f64 / spec.count.max(1) as f64;
+    let leaves = sample(rate * fill);
+    let joins = sample(rate).saturating_add(expired.len());
+    let random: Vec<String> = load.members.iter().filter(|e| *e.value() > now).take(leaves).map(|e| e.key().clone()).collect();
+    for sid in expired.iter().chain(&random) { load.leave(state, sid).await; }
+    for _ in 0..joins.min(MAX_MEMBERS.saturating_sub(load.members.len())) { load.join(state, spec, now).await; }
+    joins > 0 || !random.is_empty()
+}
+
+/// 泊松分布采样（均值较大时用正态近似），使进出次数独立波动
+fn sample(mean: f64) -> usize {
+    if mean <= 0.0 { return 0; }
+    if mean > 30.0 {
+        let z = (-2.0 * rand::random::<f64>().max(f64::MIN_POSITIVE).ln()).sqrt() * (std::f64::consts::TAU * rand::random::<f64>()).cos();
+        return (mean + z * mean.sqrt()).round().max(0.0) as usize;
+    }
+    let limit = (-mean).exp();
+    let (mut k, mut p) = (0, rand::random::<f64>());
+    while p > limit {
+        k += 1;
+        p *= rand::random::<f64>();
+    }
+    k
+}
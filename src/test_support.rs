@@ -0,0 +1,105 @@
+//! 进程内测试服务：临时端口上的完整路由 + `MemoryMetaStore`，以 `activenow_protocol::Client` 驱动连接
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use activenow_protocol::{Client, Hello, ServerMsg};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::AbortHandle;
+
+use crate::config::Config;
+use crate::gateway::{self, AppState};
+use crate::logging::LogControl;
+
+pub const ADMIN_TOKEN: &str = "test-admin";
+
+/// 默认配置（忽略宽限期），开启管理接口
+pub fn config() -> Config {
+    let mut cfg = Config::from_env();
+    cfg.admin_token = Some(ADMIN_TOKEN.to_string());
+    cfg.admin_addr = None;
+    cfg.api_tokens = Vec::new();
+    cfg.leave_grace = None;
+    cfg
+}
+
+pub struct TestServer {
+    pub state: AppState,
+    pub addr: SocketAddr,
+    serve: AbortHandle,
+}
+
+impl TestServer {
+    pub async fn start(cfg: Config) -> Self {
+        let state = crate::build_state(&cfg, LogControl::detached());
+        let admin = state.admin_token.is_some() || !state.tokens.is_empty();
+        let app = crate::app_router(&cfg, &state, admin).with_state(state.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind ephemeral port");
+        let addr = listener.local_addr().unwrap();
+        let serve = tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+        });
+        Self { state, addr, serve: serve.abort_handle() }
+    }
+
+    /// `query` 追加在 `v=2` 之后，如 `socket_session_id=s1`
+    pub fn ws_url(&self, query: &str) -> String {
+        format!("ws://{}/ws?v=2&{query}", self.addr)
+    }
+
+    pub async fn connect(&self, query: &str) -> (Client, Hello) {
+        Client::connect(&self.ws_url(query)).await.expect("connect")
+    }
+
+    /// 当前在线人数（与 `sync` 同口径）
+    pub async fn online(&self) -> usize {
+        gateway::online_stats(&self.state).await.count
+    }
+
+    /// 等待在线人数变为 `expected`，2 秒内未达到则失败
+    pub async fn wait_online(&self, expected: usize) {
+        for _ in 0..200 {
+            if self.online().await == expected { return; }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("online stayed at {}, expected {expected}", self.online().await);
+    }
+
+    /// 带管理令牌的 HTTP 请求，返回状态码与响应体
+    pub async fn http(&self, method: &str, path: &str, body: Option<&str>) -> (u16, String) {
+        let mut req = format!(
+            "{method} {path} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nAuthorization: Bearer {ADMIN_TOKEN}\r\n",
+            self.addr
+        );
+        if let Some(body) = body {
+            req.push_str(&format!("Content-Type: application/json\r\nContent-Length: {}\r\n", body.len()));
+        }
+        req.push_str("\r\n");
+        req.push_str(body.unwrap_or_default());
+        let mut stream = TcpStream::connect(self.addr).await.unwrap();
+        stream.write_all(req.as_bytes()).await.unwrap();
+        let mut raw = String::new();
+        stream.read_to_string(&mut raw).await.unwrap();
+        let status = raw.split_whitespace().nth(1).and_then(|c| c.parse().ok()).expect("status line");
+        let body = raw.split_once("\r\n\r\n").map(|(_, b)| b.to_string()).unwrap_or_default();
+        (status, body)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) { self.serve.abort(); }
+}
+
+/// 读取下一条下行消息，跳过 `sync` 等其他消息直到满足 `pick`；2 秒超时
+pub async fn next_matching<T>(client: &mut Client, mut pick: impl FnMut(ServerMsg) -> Option<T>) -> Option<T> {
+    tokio::time::timeout(Duration::from_secs(2), async {
+        while let Some(msg) = client.next().await {
+            if let Some(v) = msg.ok().and_then(&mut pick) { return Some(v); }
+        }
+        None
+    })
+    .await
+    .ok()
+    .flatten()
+}
@@ -0,0 +1,19 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 墙钟时间源；令牌过期、日统计滚动与事件时间戳均经由此处取时
+pub trait Clock: Send + Sync {
+    /// Unix 毫秒
+    fn now_ms(&self) -> u64;
+}
+
+/// 系统时钟
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+    }
+}
+
+pub type SharedClock = Arc<dyn Clock>;
@@ -0,0 +1,118 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use crate::sign;
+
+/// API 令牌权限；`admin` 包含其余全部权限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    /// 读取在线统计（`/v1/metrics/*`、`/metrics`，需 `PUBLIC_METRICS=false` 才校验）
+    #[serde(rename = "read:presence")]
+    ReadPresence,
+    /// 修改会话归属（`POST /v1/admin/sessions/{session_id}/link`）
+    #[serde(rename = "write:presence")]
+    WritePresence,
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim() {
+            "read:presence" => Some(Scope::ReadPresence),
+            "write:presence" => Some(Scope::WritePresence),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// 令牌记录；只保存哈希，明文仅在签发时返回一次
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiToken {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// 经管理接口签发的时刻；来自 `API_TOKENS` 的为 `None`
+    pub created_ms: Option<u64>,
+    #[serde(skip)]
+    hash: String,
+}
+
+impl ApiToken {
+    fn allows(&self, scope: Scope) -> bool { self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope) }
+}
+
+/// `sha1:<base64url(sha1(token))>`
+pub fn hash(token: &str) -> String {
+    format!("sha1:{}", URL_SAFE_NO_PAD.encode(Sha1::digest(token.as_bytes())))
+}
+
+/// 解析 `API_TOKENS`：逗号分隔的 `名称=权限+权限=令牌`，令牌可直接写 `sha1:...` 哈希；格式错误的条目忽略
+pub fn parse_spec(raw: &str) -> Vec<ApiToken> {
+    raw.split(',')
+        .filter_map(|item| {
+            let mut parts = item.trim().splitn(3, '=');
+            let name = parts.next()?.trim();
+            let scopes: Option<Vec<Scope>> = parts.next()?.split('+').map(Scope::parse).collect();
+            let secret = parts.next()?.trim();
+            if name.is_empty() || secret.is_empty() { return None; }
+            let hash = if secret.starts_with("sha1:") { secret.to_string() } else { hash(secret) };
+            Some(ApiToken { name: name.to_string(), scopes: scopes.filter(|s| !s.is_empty())?, created_ms: None, hash })
+        })
+        .collect()
+}
+
+/// 令牌表：按哈希查找，名称唯一
+#[derive(Default)]
+pub struct ApiTokens {
+    by_hash: DashMap<String, ApiToken>,
+}
+
+impl ApiTokens {
+    pub fn new(tokens: Vec<ApiToken>) -> Self {
+        let this = Self::default();
+        for t in tokens { this.insert(t); }
+        this
+    }
+
+    pub fn is_empty(&self) -> bool { self.by_hash.is_empty() }
+
+    /// 同名令牌被替换
+    fn insert(&self, token: ApiToken) {
+        self.revoke(&token.name);
+        self.by_hash.insert(token.hash.clone(), token);
+    }
+
+    /// 令牌有效且具备 `scope` 时返回其记录；逐条定长比较哈希，不按命中与否提前返回
+    pub fn check(&self, token: &str, scope: Scope) -> Option<ApiToken> {
+        let h = hash(token);
+        let mut found = None;
+        for t in self.by_hash.iter() {
+            if sign::ct_eq(t.hash.as_bytes(), h.as_bytes()) { found = Some(t.clone()); }
+        }
+        found.filter(|t| t.allows(scope))
+    }
+
+    /// 生成随机令牌并返回明文
+    pub fn issue(&self, name: String, scopes: Vec<Scope>, now_ms: u64) -> (String, ApiToken) {
+        let secret = format!("an_{}", nanoid::nanoid!(32));
+        let token = ApiToken { name, scopes, created_ms: Some(now_ms), hash: hash(&secret) };
+        self.insert(token.clone());
+        (secret, token)
+    }
+
+    pub fn revoke(&self, name: &str) -> bool {
+        let before = self.by_hash.len();
+        self.by_hash.retain(|_, t| t.name != name);
+        self.by_hash.len() != before
+    }
+
+    /// 按名称排序
+    pub fn list(&self) -> Vec<ApiToken> {
+        let mut v: Vec<_> = self.by_hash.iter().map(|t| t.clone()).collect();
+        v.sort_by(|a, b| a.name.cmp(&b.name));
+        v
+    }
+}
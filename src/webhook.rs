@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::sign;
+
+/// 以 HTTP/1.1 POST 推送 JSON；仅支持 `http://`（需要 HTTPS 时经本地代理转发）
+pub async fn post_json(url: &str, body: &str, extra_headers: &[(&str, String)]) -> Result<u16, String> {
+    let mut headers = vec![("Content-Type", "application/json".to_string())];
+    headers.extend_from_slice(extra_headers);
+    request("POST", url, Some(body), &headers).await
+}
+
+/// 按 `EVENT_SIGNING_SECRET` 对请求体签名，生成 `X-Signature` 头；未配置密钥时返回空列表
+pub fn signature_headers(secret: Option<&str>, body: &str) -> Vec<(&'static str, String)> {
+    secret.map(|k| ("X-Signature", sign::event_signature(k.as_bytes(), body))).into_iter().collect()
+}
+
+/// HTTP/1.1 GET，仅关心状态码（健康检查）
+pub async fn get(url: &str) -> Result<u16, String> {
+    request("GET", url, None, &[]).await
+}
+
+/// 最小 HTTP/1.1 客户端：发送请求并解析状态行，非 2xx 视为失败；5 秒超时
+async fn request(method: &str, url: &str, body: Option<&str>, extra_headers: &[(&str, String)]) -> Result<u16, String> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| format!("unsupported url: {url}"))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let addr = if authority.rsplit_once(':').is_some_and(|(_, p)| p.parse::<u16>().is_ok()) {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+    let mut req = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {authority}\r\nConnection: close\r\nUser-Agent: activenow/{}\r\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    if let Some(body) = body { req.push_str(&format!("Content-Length: {}\r\n", body.len())); }
+    for (k, v) in extra_headers { req.push_str(&format!("{k}: {v}\r\n")); }
+    req.push_str("\r\n");
+    req.push_str(body.unwrap_or_default());
+
+    let fut = async {
+        let mut stream = TcpStream::connect(&addr).await.map_err(|e| e.to_string())?;
+        stream.write_all(req.as_bytes()).await.map_err(|e| e.to_string())?;
+        let mut head = [0u8; 64];
+        let n = stream.read(&mut head).await.map_err(|e| e.to_string())?;
+        let status_line = String::from_utf8_lossy(&head[..n]);
+        status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|c| c.parse::<u16>().ok())
+            .ok_or_else(|| "malformed response".to_string())
+    };
+    let status = tokio::time::timeout(Duration::from_secs(5), fut).await.map_err(|_| "timeout".to_string())??;
+    if (200..300).contains(&status) { Ok(status) } else { Err(format!("status {status}")) }
+}
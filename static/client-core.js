@@ -0,0 +1,102 @@
+var ACTIVENOW_VERSION = "__VERSION__";
+
+function defaultUrl(base) {
+  var u = new URL(base || location.href);
+  var proto = u.protocol === "https:" ? "wss:" : "ws:";
+  return proto + "//" + u.host + "/ws";
+}
+
+var SID_KEY = "activenow:sid";
+
+function stableSessionId() {
+  try {
+    var key = SID_KEY;
+    var v = localStorage.getItem(key);
+    if (!v) {
+      v = (crypto.randomUUID && crypto.randomUUID()) || String(Math.random()).slice(2) + Date.now();
+      localStorage.setItem(key, v);
+    }
+    return v;
+  } catch (e) {
+    return undefined;
+  }
+}
+
+/**
+ * 连接 ActiveNow 并订阅在线人数
+ * opts: { url, sessionId, version, observe, events, maxBackoffMs, onCount(count), onEvent(msg) }
+ * 返回 { onCount, onEvent, close, count }
+ */
+function connect(opts) {
+  opts = opts || {};
+  var countCbs = opts.onCount ? [opts.onCount] : [];
+  var eventCbs = opts.onEvent ? [opts.onEvent] : [];
+  var url = opts.url || defaultUrl(opts.base);
+  var sessionId = opts.sessionId || stableSessionId();
+  var maxBackoff = opts.maxBackoffMs || 30000;
+  var attempt = 0;
+  var closed = false;
+  var ws = null;
+  var timer = null;
+  var nextDelay = null;
+  var resumeToken = null;
+  var handle = {
+    count: null,
+    onCount: function (cb) { countCbs.push(cb); if (handle.count !== null) cb(handle.count); return handle; },
+    onEvent: function (cb) { eventCbs.push(cb); return handle; },
+    close: function () { closed = true; clearTimeout(timer); if (ws) ws.close(); }
+  };
+
+  function target() {
+    var u = new URL(url);
+    if (sessionId) u.searchParams.set("socket_session_id", sessionId);
+    if (opts.version) u.searchParams.set("v", String(opts.version));
+    if (opts.observe) u.searchParams.set("observe", "1");
+    if (opts.events) u.searchParams.set("events", [].concat(opts.events).join(","));
+    if (resumeToken) u.searchParams.set("resume", resumeToken);
+    if (document.referrer) u.searchParams.set("ref", document.referrer);
+    return u.toString();
+  }
+
+  function schedule() {
+    if (closed) return;
+    // 服务端建议的等待优先，否则指数退避；均加抖动，避免大量客户端同时重连
+    var base = nextDelay !== null ? nextDelay : Math.min(maxBackoff, 500 * Math.pow(2, attempt));
+    var delay = base * (0.5 + Math.random() / 2);
+    nextDelay = null;
+    attempt += 1;
+    timer = setTimeout(open, delay);
+  }
+
+  function open() {
+    if (closed) return;
+    ws = new WebSocket(target());
+    ws.onmessage = function (e) {
+      var msg;
+      try { msg = JSON.parse(e.data); } catch (err) { return; }
+      if (msg.type === "hello") {
+        attempt = 0;
+        resumeToken = msg.resume_token || null;
+        // 服务端启用签名会话 ID 时改用其下发的值
+        if (msg.session_id && !opts.sessionId) {
+          sessionId = msg.session_id;
+          try { localStorage.setItem(SID_KEY, sessionId); } catch (err) {}
+        }
+      }
+      if (msg.type === "closing") {
+        if (msg.url) url = msg.url;
+        if (msg.retry_after_ms === null) closed = true;
+        else nextDelay = msg.retry_after_ms;
+      }
+      if (typeof msg.count === "number" && (msg.type === "hello" || msg.type === "sync")) {
+        handle.count = msg.count;
+        countCbs.forEach(function (cb) { cb(msg.count); });
+      }
+      eventCbs.forEach(function (cb) { cb(msg); });
+    };
+    ws.onclose = schedule;
+  }
+
+  open();
+  return handle;
+}